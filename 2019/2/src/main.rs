@@ -1,109 +1,222 @@
+use anyhow::{anyhow, Context, Result};
+use intcode::trace::Format;
+use intcode::{
+    disassemble, evaluate_symbolic, parse_program, Debugger, Profile, Snapshot, Tracer, Vm,
+    VmError,
+};
+use std::fs::File;
 use std::io;
-use std::io::BufRead;
-use anyhow::{Result, anyhow};
-use std::time::Instant;
+use std::io::{BufRead, BufWriter};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use structopt::StructOpt;
 
-fn main() -> Result<()> {
+fn parse_range(text: &str) -> Result<Range<i64>> {
+    let (start, end) = text
+        .split_once("..")
+        .ok_or_else(|| anyhow!("Expected a range like 0..100, found {}", text))?;
+    Ok(start.trim().parse()?..end.trim().parse()?)
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "Problem2", about = "Solving AOC 2019 problem 2.")]
+struct Args {
+    /// The value the program should leave at address 0
+    #[structopt(long, default_value = "19690720")]
+    target: i64,
+    /// Nouns to try, as start..end
+    #[structopt(long, default_value = "0..100", parse(try_from_str = parse_range))]
+    nouns: Range<i64>,
+    /// Verbs to try, as start..end
+    #[structopt(long, default_value = "0..100", parse(try_from_str = parse_range))]
+    verbs: Range<i64>,
+    /// How many threads to search with, one per CPU by default
+    #[structopt(long)]
+    threads: Option<usize>,
+    /// Solve the expression the program computes instead of searching
+    #[structopt(long)]
+    symbolic: bool,
+    /// Print the disassembled program
+    #[structopt(long)]
+    disassemble: bool,
+    /// Step through the program with an optional noun and verb, taking
+    /// debugger commands from the rest of stdin
+    #[structopt(long, min_values = 0, max_values = 2)]
+    debug: Option<Vec<i64>>,
+    /// Run once, recording every instruction to this file
+    #[structopt(long, parse(from_os_str))]
+    trace: Option<PathBuf>,
+    /// How to write the trace, jsonl or binary
+    #[structopt(long, default_value = "jsonl")]
+    trace_format: Format,
+    /// Run once and print where the program spent its instructions
+    #[structopt(long)]
+    profile: bool,
+    /// The noun for a traced or profiled run, rather than the program's own
+    #[structopt(long)]
+    noun: Option<i64>,
+    /// The verb for a traced or profiled run, rather than the program's own
+    #[structopt(long)]
+    verb: Option<i64>,
+}
 
+fn main() -> Result<()> {
+    let args = Args::from_args();
     let stdin = io::stdin();
     let mut buffer = String::new();
     let mut handle = stdin.lock();
     handle.read_line(&mut buffer)?;
+    let program = parse_program(&buffer)?;
 
-    let program : Vec<usize> = buffer.split(",")
-	.flat_map(|l| l.parse::<usize>()).collect();
-    let now = Instant::now();
-
-    match naive_solve(&program) {
-	Ok((noun, verb)) => {println!("Answer: {} {} {}", noun, verb, (noun * 100 + verb)); Ok(()) },
-	Err(e) => Err(e)
+    if args.disassemble {
+        print!("{}", disassemble(&program));
+        return Ok(());
+    }
+    if let Some(values) = args.debug {
+        let mut vm = Vm::new(program);
+        for (address, value) in values.into_iter().enumerate() {
+            vm.memory.set(address + 1, value)?;
+        }
+        Debugger::new(vm).repl(handle, io::stdout())?;
+        return Ok(());
     }
-}
 
-fn naive_solve(program : &Vec<usize>) -> Result<(usize, usize)> {
-    for noun in 0..100 {
-	for verb in 0..100 {
-	    match run_program(program, noun, verb) {
-		Ok(result) => {
-		    if result == 19690720 {
-			return Ok((noun, verb))
-		    }
-		},
-		_ => {}
-	    }
-	}
+    if args.trace.is_some() || args.profile {
+        let mut vm = Vm::new(program);
+        if let Some(noun) = args.noun {
+            vm.memory.set(1, noun)?;
+        }
+        if let Some(verb) = args.verb {
+            vm.memory.set(2, verb)?;
+        }
+        let mut profile = Profile::new();
+        match &args.trace {
+            Some(path) => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut tracer = Tracer::new(file, args.trace_format);
+                vm.run_observed(&mut (&mut tracer, &mut profile))?;
+                tracer.finish()?;
+            }
+            None => vm.run_observed(&mut profile)?,
+        }
+        if args.profile {
+            print!("{}", profile);
+        }
+        println!("Result: {}", vm.memory.get(0));
+        return Ok(());
     }
-    return Err(anyhow!("Couldn't find solution"));
-}
 
-fn run_program(program : &Vec<usize>, noun: usize, verb: usize) -> Result<usize> {
-    let mut attempt = program.clone();
-    attempt[1] = noun;
-    attempt[2] = verb;
-    evaluate_program(attempt)
+    let (noun, verb) = if args.symbolic {
+        symbolic_solve(&program, args.target, args.nouns, args.verbs)?
+    } else {
+        let threads = args
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()));
+        search_solve(&program, args.target, args.nouns, args.verbs, threads)?
+    };
+    println!("Answer: {} {} {}", noun, verb, (noun * 100 + verb));
+    Ok(())
 }
 
-#[derive(Debug)]
-struct Instruction {
-    addr_arg1: usize,
-    addr_arg2: usize,
-    addr_dest: usize,
-    opcode: usize
+/// Try every noun and verb, with each thread taking every `threads`th noun.
+/// Like a serial search, the answer is the solution with the lowest noun,
+/// then the lowest verb, however the threads are scheduled.
+fn search_solve(
+    program: &[i64],
+    target: i64,
+    nouns: Range<i64>,
+    verbs: Range<i64>,
+    threads: usize,
+) -> Result<(i64, i64)> {
+    // Every attempt forks from the same snapshot, only copying the memory
+    // it writes to
+    let start = Vm::new(program.to_vec()).snapshot();
+    let found = Mutex::new(None);
+    // Each noun belongs to one thread, which tries its verbs in order, so
+    // only nouns past the lowest solved one can be skipped
+    let best_noun = AtomicI64::new(i64::MAX);
+    thread::scope(|scope| {
+        for offset in 0..threads.max(1) {
+            let (start, found, best_noun) = (&start, &found, &best_noun);
+            let (nouns, verbs) = (nouns.clone(), verbs.clone());
+            scope.spawn(move || {
+                for noun in nouns.skip(offset).step_by(threads.max(1)) {
+                    if noun > best_noun.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    for verb in verbs.clone() {
+                        if run_program(start, noun, verb).ok() == Some(target) {
+                            let mut found = found.lock().expect("Search thread panicked");
+                            if found.is_none_or(|best| (noun, verb) < best) {
+                                *found = Some((noun, verb));
+                            }
+                            best_noun.fetch_min(noun, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    found
+        .into_inner()
+        .expect("Search thread panicked")
+        .ok_or_else(|| anyhow!("Couldn't find solution"))
 }
 
-#[derive(Debug)]
-struct ProgramMod {
-    to_store: usize,
-    destination: usize,
+/// Work out what the program leaves at address 0 in terms of the noun and
+/// verb, then solve for the target directly. The model only covers the
+/// arithmetic, so each solution is run to check the VM agrees.
+fn symbolic_solve(
+    program: &[i64],
+    target: i64,
+    nouns: Range<i64>,
+    verbs: Range<i64>,
+) -> Result<(i64, i64)> {
+    let result = evaluate_symbolic(program, &[1, 2], 0)
+        .context("Program isn't linear in the noun and verb")?;
+    let (noun_factor, verb_factor) = (result.coefficient(1), result.coefficient(2));
+    let overflow = || anyhow!("{} = {} overflows an i64", result, target);
+    let start = Vm::new(program.to_vec()).snapshot();
+    for noun in nouns {
+        let remaining = noun_factor
+            .checked_mul(noun)
+            .and_then(|noun_part| target.checked_sub(result.constant)?.checked_sub(noun_part))
+            .ok_or_else(overflow)?;
+        let verb = match verb_factor {
+            0 if remaining == 0 => verbs.start,
+            0 => continue,
+            factor => match remaining.checked_rem(factor).ok_or_else(overflow)? {
+                0 => remaining.checked_div(factor).ok_or_else(overflow)?,
+                _ => continue,
+            },
+        };
+        if verbs.contains(&verb) && run_program(&start, noun, verb).ok() == Some(target) {
+            return Ok((noun, verb));
+        }
+    }
+    Err(anyhow!("No noun and verb make {} = {}", result, target))
 }
 
-fn process_instruction(i : &Instruction, program : &Vec<usize> ) -> Result<ProgramMod> {
-    let l = program.len();
-    if i.addr_arg1 < l && i.addr_arg2 < l && i.addr_dest < l {
-	let destination = i.addr_dest;
-	let op1 = program[i.addr_arg1];
-	let op2 = program[i.addr_arg2];
-	let to_store = match i.opcode {
-	    1 => Ok(op1 + op2),
-	    2 => Ok(op1 * op2),
-	    _ => { Err(anyhow!("Invalid OPCODE")) },
-	}?;
-	// println!("Creating Program Mod {:?}", ProgramMod{to_store, destination});
-	Ok(ProgramMod{to_store, destination})
-    } else {
-	Err(anyhow!("Out of bounds -- SEGFAULT"))
-    }
+fn run_program(start: &Snapshot, noun: i64, verb: i64) -> Result<i64, VmError> {
+    let mut vm = start.fork();
+    vm.memory.set(1, noun)?;
+    vm.memory.set(2, verb)?;
+    evaluate(vm)
 }
 
+/// Run the program and return whatever it left at address 0
+#[cfg(test)]
+fn evaluate_program(program: Vec<i64>) -> Result<i64, VmError> {
+    evaluate(Vm::new(program))
+}
 
-fn evaluate_program(mut program : Vec<usize>) -> Result<usize> {
-    let mut idx = 0;
-    while idx < program.len() {
-	let opcode = program[idx];
-	match opcode {
-	    1 | 2 => {
-		// Check if idx can go over...
-		if idx + 3 > program.len() {
-		    return Err(anyhow!("Out of bounds!"));
-		}
-		let i = Instruction {
-		    opcode,
-		    addr_arg1: program[idx + 1],
-		    addr_arg2: program[idx + 2],
-		    addr_dest: program[idx + 3],
-		};
-		// println!("Applying Instruction {:?}", i);
-		let program_mod = process_instruction(&i, &program)?;
-		program[program_mod.destination] = program_mod.to_store;
-	    },
-	    99 => {
-		return Ok(program[0]);
-	    },
-	    invalid_opcode => {return Err(anyhow!("Invalid opcode {}", invalid_opcode)) },
-	};
-	idx += 4;
-    }
-    return Err(anyhow!("Invalid Program... No Exit code!"));
+fn evaluate(mut vm: Vm) -> Result<i64, VmError> {
+    vm.run()?;
+    Ok(vm.memory.get(0))
 }
 
 #[test]
@@ -112,3 +225,75 @@ fn check_example_programs() {
     assert_eq!(evaluate_program(vec!(2,3,0,3,99)).unwrap(), 2);
     assert_eq!(evaluate_program(vec!(1,1,1,4,99,5,6,0,99)).unwrap(), 30);
 }
+
+#[test]
+fn check_assembled_programs() {
+    let program = intcode::assemble(
+        "
+        mul [x], [y], [0]
+        hlt
+    x:  db 6
+    y:  db 7
+    ",
+    )
+    .unwrap();
+    assert_eq!(evaluate_program(program).unwrap(), 42);
+}
+
+#[test]
+fn check_solvers_agree() {
+    // Like the puzzle input, the first instruction reads through the noun and
+    // verb before its result is overwritten
+    let program = intcode::assemble(
+        "
+                add [0], [0], [3]
+                mul [1], 100, [3]
+                add [3], [2], [3]
+                add [3], 5, [0]
+                hlt
+        ",
+    )
+    .unwrap();
+    let target = 100 * 40 + 17 + 5;
+    let expected = (40, 17);
+    assert_eq!(
+        search_solve(&program, target, 0..100, 0..100, 1).unwrap(),
+        expected
+    );
+    assert_eq!(
+        search_solve(&program, target, 0..100, 0..100, 4).unwrap(),
+        expected
+    );
+    assert_eq!(
+        symbolic_solve(&program, target, 40..100, 0..100).unwrap(),
+        expected
+    );
+    assert!(symbolic_solve(&program, target, 41..100, 0..100).is_err());
+    assert!(symbolic_solve(&program, i64::MIN, 0..100, 0..100).is_err());
+    // The algebra is happy with a noun of -1, but the VM can't read address -1
+    assert_eq!(
+        search_solve(&program, target, -1..1, 0..5000, 1).unwrap(),
+        (0, 4017)
+    );
+    assert_eq!(
+        symbolic_solve(&program, target, -1..1, 0..5000).unwrap(),
+        (0, 4017)
+    );
+
+    // Many nouns and verbs add up to 50, and every thread count should find
+    // the same one
+    let program = intcode::assemble(
+        "
+                add [0], [0], [3]
+                add [1], [2], [0]
+                hlt
+        ",
+    )
+    .unwrap();
+    for threads in 1..=8 {
+        assert_eq!(
+            search_solve(&program, 50, 0..100, 0..100, threads).unwrap(),
+            (0, 50)
+        );
+    }
+}
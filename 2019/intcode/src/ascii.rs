@@ -0,0 +1,116 @@
+//! Talking to programs that speak ASCII.
+//!
+//! Input is sent a line at a time, each character as its code followed by a
+//! newline (10). Output values in the ASCII range are printed as characters,
+//! and anything else, usually a puzzle answer, as a plain number.
+use crate::vm::State;
+use crate::{Vm, VmError};
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone)]
+pub struct Ascii {
+    pub vm: Vm,
+}
+
+/// How a single output value is shown
+pub fn render(value: i64) -> String {
+    match value {
+        0..=127 => (value as u8 as char).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+impl Ascii {
+    pub fn new(vm: Vm) -> Self {
+        Ascii { vm }
+    }
+
+    /// Queue a line of input, adding the newline
+    pub fn send_line(&mut self, line: &str) {
+        self.vm.input.extend(line.chars().map(|c| c as i64));
+        self.vm.input.push_back(10);
+    }
+
+    /// Run until the program halts or wants another line, returning what it
+    /// printed along the way
+    pub fn run(&mut self) -> Result<(String, State), VmError> {
+        let mut text = String::new();
+        loop {
+            match self.vm.resume()? {
+                State::Output(value) => text.push_str(&render(value)),
+                state => return Ok((text, state)),
+            }
+        }
+    }
+
+    /// Play the program from `input`, a line at a time, until it halts or
+    /// `input` runs out
+    pub fn play<R, W>(&mut self, input: R, mut output: W) -> io::Result<State>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let vm_error = |error: VmError| io::Error::other(error);
+        let mut lines = input.lines();
+        loop {
+            let (text, state) = self.run().map_err(vm_error)?;
+            write!(output, "{}", text)?;
+            output.flush()?;
+            if state == State::Halted {
+                return Ok(state);
+            }
+            match lines.next() {
+                Some(line) => self.send_line(&line?),
+                None => return Ok(state),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    fn echo() -> Ascii {
+        // Prints a prompt, echoes one line back, then prints a big number
+        let program = assemble(
+            "
+                    out 62
+                    out 32
+            loop:   in [c]
+                    out [c]
+                    eq [c], 10, [done]
+                    jz [done], loop
+                    out 1000
+                    out 10
+                    hlt
+            c:      db 0
+            done:   db 0
+            ",
+        )
+        .unwrap();
+        Ascii::new(Vm::new(program))
+    }
+
+    #[test]
+    fn lines_in_text_out() {
+        let mut ascii = echo();
+        assert_eq!(ascii.run(), Ok(("> ".to_string(), State::NeedsInput)));
+        ascii.send_line("hi");
+        assert_eq!(ascii.run(), Ok(("hi\n1000\n".to_string(), State::Halted)));
+        assert_eq!(render(-1), "-1");
+    }
+
+    #[test]
+    fn plays_from_a_reader() {
+        let mut output = Vec::new();
+        let state = echo().play("hello\nignored\n".as_bytes(), &mut output);
+        assert_eq!(state.unwrap(), State::Halted);
+        assert_eq!(String::from_utf8(output).unwrap(), "> hello\n1000\n");
+
+        let mut output = Vec::new();
+        let state = echo().play("".as_bytes(), &mut output);
+        assert_eq!(state.unwrap(), State::NeedsInput);
+    }
+}
@@ -0,0 +1,416 @@
+//! A small assembly language for writing Intcode by hand.
+//!
+//! ```text
+//! ; Count down from the input, printing each number
+//! const STEP = -1
+//!         in [n]
+//! loop:   out [n]
+//!         add [n], STEP, [n]
+//!         jnz [n], loop
+//!         hlt
+//! n:      db 0
+//! ```
+//!
+//! Operands are immediate (`5`), position (`[5]`) or relative to the relative
+//! base (`[rb+5]`, `[rb-5]`). Anywhere a number goes, a label, a constant or
+//! a sum like `n+1` can go instead. `db` lays out integers as they are.
+//! Everything after a `;` is a comment. The disassembler's listings are valid
+//! input.
+use crate::instruction::{Instruction, Mode, Opcode, Parameter};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongArity {
+        mnemonic: String,
+        expected: usize,
+        found: usize,
+    },
+    BadOperand(String),
+    BadName(String),
+    UndefinedSymbol(String),
+    DuplicateSymbol(String),
+    WriteToImmediate,
+    /// An expression's value doesn't fit in an i64
+    Overflow,
+}
+
+/// What went wrong, and on which line (starting at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(mnemonic) => write!(f, "Unknown mnemonic {}", mnemonic),
+            AsmErrorKind::WrongArity {
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} operands, found {}",
+                mnemonic, expected, found
+            ),
+            AsmErrorKind::BadOperand(operand) => write!(f, "Can't parse operand {:?}", operand),
+            AsmErrorKind::BadName(name) => write!(f, "{:?} isn't a valid name", name),
+            AsmErrorKind::UndefinedSymbol(name) => write!(f, "{} isn't defined", name),
+            AsmErrorKind::DuplicateSymbol(name) => write!(f, "{} is defined twice", name),
+            AsmErrorKind::WriteToImmediate => write!(f, "Can't write to an immediate operand"),
+            AsmErrorKind::Overflow => write!(f, "Expression overflows an i64"),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Sum of numbers and symbols, like `n+1` or `-4`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expr {
+    terms: Vec<(i64, Term)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Operand {
+    mode: Mode,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Instruction(Opcode, Vec<Operand>),
+    Data(Vec<Expr>),
+    Const(String, Expr),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(opcode, _) => 1 + opcode.arity(),
+            Statement::Data(values) => values.len(),
+            Statement::Const(..) => 0,
+        }
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && text != "rb"
+}
+
+fn parse_expr(text: &str) -> Option<Expr> {
+    let text = text.replace(char::is_whitespace, "");
+    if text.is_empty() {
+        return None;
+    }
+    // Split before every sign that isn't at the start
+    let mut terms = Vec::new();
+    let mut start = 0;
+    for (at, c) in text.char_indices().skip(1) {
+        if c == '+' || c == '-' {
+            terms.push(&text[start..at]);
+            start = at;
+        }
+    }
+    terms.push(&text[start..]);
+
+    terms
+        .into_iter()
+        .map(|term| {
+            // Numbers keep their sign, so i64::MIN can be written
+            if let Ok(number) = term.parse() {
+                return Some((1, Term::Number(number)));
+            }
+            let (sign, term) = match term.as_bytes().first() {
+                Some(b'+') => (1, &term[1..]),
+                Some(b'-') => (-1, &term[1..]),
+                _ => (1, term),
+            };
+            if is_name(term) {
+                Some((sign, Term::Symbol(term.to_string())))
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|terms| Expr { terms })
+}
+
+fn parse_operand(text: &str) -> Option<Operand> {
+    let text = text.trim();
+    let inner = match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(inner) => inner.trim(),
+        None => {
+            return Some(Operand {
+                mode: Mode::Immediate,
+                expr: parse_expr(text)?,
+            })
+        }
+    };
+    match inner.strip_prefix("rb") {
+        Some(offset) if offset.trim().is_empty() => Some(Operand {
+            mode: Mode::Relative,
+            expr: parse_expr("0")?,
+        }),
+        Some(offset) if offset.trim_start().starts_with(['+', '-']) => Some(Operand {
+            mode: Mode::Relative,
+            expr: parse_expr(offset)?,
+        }),
+        _ => Some(Operand {
+            mode: Mode::Position,
+            expr: parse_expr(inner)?,
+        }),
+    }
+}
+
+fn parse_statement(text: &str) -> Result<Statement, AsmErrorKind> {
+    let (word, rest) = match text.find(char::is_whitespace) {
+        Some(at) => (&text[..at], text[at..].trim()),
+        None => (text, ""),
+    };
+    let operands = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',').map(str::trim).collect::<Vec<_>>()
+    };
+
+    match word {
+        "db" => operands
+            .iter()
+            .map(|operand| {
+                parse_expr(operand).ok_or_else(|| AsmErrorKind::BadOperand(operand.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Statement::Data),
+        "const" => {
+            let (name, value) = rest
+                .split_once('=')
+                .ok_or_else(|| AsmErrorKind::BadOperand(rest.to_string()))?;
+            let name = name.trim();
+            if !is_name(name) {
+                return Err(AsmErrorKind::BadName(name.to_string()));
+            }
+            let value =
+                parse_expr(value).ok_or_else(|| AsmErrorKind::BadOperand(value.to_string()))?;
+            Ok(Statement::Const(name.to_string(), value))
+        }
+        _ => {
+            let opcode = Opcode::from_mnemonic(word)
+                .ok_or_else(|| AsmErrorKind::UnknownMnemonic(word.to_string()))?;
+            if operands.len() != opcode.arity() {
+                return Err(AsmErrorKind::WrongArity {
+                    mnemonic: word.to_string(),
+                    expected: opcode.arity(),
+                    found: operands.len(),
+                });
+            }
+            let operands = operands
+                .iter()
+                .map(|operand| {
+                    parse_operand(operand)
+                        .ok_or_else(|| AsmErrorKind::BadOperand(operand.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(written) = opcode.writes() {
+                if operands[written].mode == Mode::Immediate {
+                    return Err(AsmErrorKind::WriteToImmediate);
+                }
+            }
+            Ok(Statement::Instruction(opcode, operands))
+        }
+    }
+}
+
+fn evaluate(expr: &Expr, symbols: &HashMap<String, i64>) -> Result<i64, AsmErrorKind> {
+    expr.terms.iter().try_fold(0i64, |total, (sign, term)| {
+        let value = match term {
+            Term::Number(number) => *number,
+            Term::Symbol(name) => *symbols
+                .get(name)
+                .ok_or_else(|| AsmErrorKind::UndefinedSymbol(name.clone()))?,
+        };
+        sign.checked_mul(value)
+            .and_then(|value| total.checked_add(value))
+            .ok_or(AsmErrorKind::Overflow)
+    })
+}
+
+fn define(symbols: &mut HashMap<String, i64>, name: &str, value: i64) -> Result<(), AsmErrorKind> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(AsmErrorKind::DuplicateSymbol(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Assemble source into a program. Labels can be used before they're
+/// defined, constants only after.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let error = |line: usize| {
+        move |kind| AsmError {
+            line: line + 1,
+            kind,
+        }
+    };
+
+    // First pass: parse every line and give every label an address
+    let mut symbols = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+    for (line, text) in source.lines().enumerate() {
+        let mut text = text.split(';').next().unwrap_or("").trim();
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_name(label) {
+                return Err(error(line)(AsmErrorKind::BadName(label.to_string())));
+            }
+            define(&mut symbols, label, address as i64).map_err(error(line))?;
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(text).map_err(error(line))?;
+        address += statement.size();
+        statements.push((line, statement));
+    }
+
+    // Second pass: work out every operand
+    let mut program = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Const(name, value) => {
+                let value = evaluate(&value, &symbols).map_err(error(line))?;
+                define(&mut symbols, &name, value).map_err(error(line))?;
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    program.push(evaluate(&value, &symbols).map_err(error(line))?);
+                }
+            }
+            Statement::Instruction(opcode, operands) => {
+                let parameters = operands
+                    .iter()
+                    .map(|operand| {
+                        Ok(Parameter {
+                            mode: operand.mode,
+                            value: evaluate(&operand.expr, &symbols)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error(line))?;
+                program.extend(Instruction::new(opcode, &parameters).encode());
+            }
+        }
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disassemble, run};
+
+    const COUNTDOWN: &str = "
+        ; Count down from the input, printing each number
+        const STEP = -1
+                in [n]
+        loop:   out [n]
+                add [n], STEP, [n]
+                jnz [n], loop
+                hlt
+        n:      db 0
+    ";
+
+    #[test]
+    fn assembles_and_runs() {
+        let program = assemble(COUNTDOWN).unwrap();
+        assert_eq!(
+            program,
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0]
+        );
+        assert_eq!(run(program, vec![3]), Ok(vec![3, 2, 1]));
+    }
+
+    #[test]
+    fn relative_operands() {
+        let program = assemble("arb 10\nout [rb-3]\nadd [rb], [rb+2], [rb + 1]\nhlt").unwrap();
+        assert_eq!(program, vec![109, 10, 204, -3, 22201, 0, 2, 1, 99]);
+    }
+
+    #[test]
+    fn round_trips_through_the_disassembler() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let listing = disassemble(&program).to_string();
+        assert_eq!(assemble(&listing), Ok(program));
+
+        let quine =
+            crate::parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99")
+                .unwrap();
+        let listing = disassemble(&quine).to_string();
+        assert_eq!(assemble(&listing), Ok(quine));
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        let error = |source| assemble(source).unwrap_err();
+        assert_eq!(
+            error("hlt\nfoo 1"),
+            AsmError {
+                line: 2,
+                kind: AsmErrorKind::UnknownMnemonic("foo".to_string())
+            }
+        );
+        assert_eq!(
+            error("out [x]").kind,
+            AsmErrorKind::UndefinedSymbol("x".to_string())
+        );
+        assert_eq!(error("in 5").kind, AsmErrorKind::WriteToImmediate);
+        assert_eq!(
+            error("a: hlt\na: hlt").kind,
+            AsmErrorKind::DuplicateSymbol("a".to_string())
+        );
+        assert_eq!(
+            error("add 1, 2").kind,
+            AsmErrorKind::WrongArity {
+                mnemonic: "add".to_string(),
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            error("hlt\ndb 9223372036854775807+1"),
+            AsmError {
+                line: 2,
+                kind: AsmErrorKind::Overflow
+            }
+        );
+        assert_eq!(
+            error("const MIN = -9223372036854775808\ndb -MIN").kind,
+            AsmErrorKind::Overflow
+        );
+    }
+
+    #[test]
+    fn signed_numbers() {
+        assert_eq!(
+            assemble("db -9223372036854775808, +5, 3-4, -2+x\nx: db 9223372036854775807"),
+            Ok(vec![i64::MIN, 5, -1, 2, i64::MAX])
+        );
+    }
+}
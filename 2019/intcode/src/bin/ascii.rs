@@ -0,0 +1,18 @@
+//! Play an ASCII Intcode program from the terminal:
+//! `cargo run --bin ascii -- program.txt`
+use intcode::{parse_program, Ascii, State, Vm};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: ascii <program>")?;
+    let program = parse_program(&fs::read_to_string(path)?)?;
+    let mut ascii = Ascii::new(Vm::new(program));
+    let stdin = io::stdin();
+    if ascii.play(stdin.lock(), io::stdout())? == State::NeedsInput {
+        eprintln!("\nOut of input, but the program wanted more");
+    }
+    Ok(())
+}
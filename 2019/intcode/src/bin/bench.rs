@@ -0,0 +1,71 @@
+//! Time the cached engine against decoding every instruction afresh:
+//! `cargo run --release --bin bench -- [program.txt [input...]]`
+//!
+//! Without a program, runs a built in loop of about ten million
+//! instructions.
+use intcode::{assemble, parse_program, Vm, VmError};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Sums i * j for every i and j below 1000
+const NESTED_LOOP: &str = "
+            in [n]
+    outer:  add [n], 0, [j]
+    inner:  mul [i], [j], [product]
+            add [sum], [product], [sum]
+            add [j], -1, [j]
+            jnz [j], inner
+            add [i], 1, [i]
+            lt [i], [n], [more]
+            jnz [more], outer
+            out [sum]
+            hlt
+    n:       db 0
+    i:       db 0
+    j:       db 0
+    product: db 0
+    sum:     db 0
+    more:    db 0
+";
+
+fn time<F>(vm: &Vm, step: F) -> Result<(Duration, Vm), VmError>
+where
+    F: Fn(&mut Vm) -> Result<(), VmError>,
+{
+    // Fresh memory, so neither engine gets pages the other has touched
+    let mut vm = Vm::new(vm.memory.to_vec()).with_input(vm.input.iter().copied());
+    let start = Instant::now();
+    while !vm.halted() {
+        step(&mut vm)?;
+    }
+    Ok((start.elapsed(), vm))
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let vm = match args.split_first() {
+        Some((path, input)) => Vm::new(parse_program(&fs::read_to_string(path)?)?).with_input(
+            input
+                .iter()
+                .map(|value| value.parse())
+                .collect::<Result<Vec<i64>, _>>()?,
+        ),
+        None => Vm::new(assemble(NESTED_LOOP)?).with_input(vec![1000]),
+    };
+
+    let (uncached, expected) = time(&vm, Vm::step_uncached)?;
+    let (cached, actual) = time(&vm, Vm::step)?;
+    if actual != expected {
+        return Err("The engines disagree".into());
+    }
+    println!("Output:   {:?}", actual.output);
+    println!("Uncached: {:>10.2?}", uncached);
+    println!(
+        "Cached:   {:>10.2?} ({:.1}x)",
+        cached,
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+    Ok(())
+}
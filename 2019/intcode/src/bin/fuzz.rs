@@ -0,0 +1,20 @@
+//! Check the VM against the reference interpreter on generated programs:
+//! `cargo run --release --bin fuzz -- [cases [seed]]`
+use intcode::differential::fuzz;
+use std::env;
+use std::error::Error;
+use std::process;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let cases = args.first().map_or(Ok(100_000), |cases| cases.parse())?;
+    let seed = args.get(1).map_or(Ok(2019), |seed| seed.parse())?;
+    match fuzz(seed, cases) {
+        Ok(()) => println!("{} programs agree", cases),
+        Err(divergence) => {
+            println!("Divergence, minimized:\n{}", divergence);
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,386 @@
+//! Stepping through a program by hand.
+//!
+//! `Debugger` wraps a VM with breakpoints and watchpoints, and `repl` drives
+//! it with commands read from any reader, usually stdin.
+use crate::instruction::Opcode;
+use crate::{Instruction, Vm, VmError, MAX_ADDRESS};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Values per line in a memory dump
+const DUMP_WIDTH: usize = 8;
+
+/// Instructions shown by `list` when not told otherwise
+const LIST_LENGTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Breakpoint {
+    /// Stop before executing the instruction at this address
+    Address(usize),
+    /// Stop before executing any instruction with this opcode
+    Opcode(Opcode),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Address(address) => write!(f, "address {}", address),
+            Breakpoint::Opcode(opcode) => write!(f, "opcode {}", opcode.mnemonic()),
+        }
+    }
+}
+
+/// Why the debugger handed control back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// Ran the requested number of steps
+    Stepped,
+    Breakpoint(Breakpoint),
+    Watchpoint {
+        address: usize,
+        old: i64,
+        new: i64,
+    },
+    Halted,
+    Error(VmError),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Stepped => write!(f, "Stepped"),
+            Stop::Breakpoint(breakpoint) => write!(f, "Hit breakpoint on {}", breakpoint),
+            Stop::Watchpoint { address, old, new } => {
+                write!(f, "Address {} changed from {} to {}", address, old, new)
+            }
+            Stop::Halted => write!(f, "Halted"),
+            Stop::Error(error) => write!(f, "Error: {}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub vm: Vm,
+    breakpoints: BTreeSet<Breakpoint>,
+    /// Watched addresses and the value they had after the last step
+    watchpoints: BTreeMap<usize, i64>,
+}
+
+impl Debugger {
+    pub fn new(vm: Vm) -> Self {
+        Debugger {
+            vm,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        self.breakpoints.remove(breakpoint)
+    }
+
+    pub fn watch(&mut self, address: usize) {
+        self.watchpoints
+            .insert(address, self.vm.memory.get(address));
+    }
+
+    pub fn unwatch(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    /// The breakpoint matching the instruction about to run, if any
+    fn breakpoint(&self) -> Option<Breakpoint> {
+        let at_address = Breakpoint::Address(self.vm.ip);
+        if self.breakpoints.contains(&at_address) {
+            return Some(at_address);
+        }
+        let opcode = Breakpoint::Opcode(self.vm.current().ok()?.opcode);
+        self.breakpoints.get(&opcode).copied()
+    }
+
+    /// Execute one instruction, ignoring breakpoints
+    fn step_once(&mut self) -> Option<Stop> {
+        if self.vm.halted() {
+            return Some(Stop::Halted);
+        }
+        if let Err(error) = self.vm.step() {
+            return Some(Stop::Error(error));
+        }
+        for (address, old) in self.watchpoints.iter_mut() {
+            let new = self.vm.memory.get(*address);
+            if new != *old {
+                let stop = Stop::Watchpoint {
+                    address: *address,
+                    old: *old,
+                    new,
+                };
+                *old = new;
+                return Some(stop);
+            }
+        }
+        if self.vm.halted() {
+            return Some(Stop::Halted);
+        }
+        None
+    }
+
+    /// Execute up to `count` instructions, stopping early at watchpoints,
+    /// errors or the end of the program
+    pub fn step(&mut self, count: usize) -> Stop {
+        for _ in 0..count {
+            if let Some(stop) = self.step_once() {
+                return stop;
+            }
+        }
+        Stop::Stepped
+    }
+
+    /// Run until something stops us. A breakpoint on the current
+    /// instruction doesn't count, so continuing from one makes progress.
+    pub fn run(&mut self) -> Stop {
+        loop {
+            if let Some(stop) = self.step_once() {
+                return stop;
+            }
+            if let Some(breakpoint) = self.breakpoint() {
+                return Stop::Breakpoint(breakpoint);
+            }
+        }
+    }
+
+    /// The instruction pointer, relative base and pending I/O
+    pub fn registers(&self) -> String {
+        format!(
+            "ip={} rb={} input={:?} output={:?}",
+            self.vm.ip, self.vm.relative_base, self.vm.input, self.vm.output
+        )
+    }
+
+    /// `length` cells of memory from `start`, a row of values per line.
+    /// Nothing past `MAX_ADDRESS` is shown.
+    pub fn dump(&self, start: usize, length: usize) -> String {
+        let mut dump = String::new();
+        let end = start.saturating_add(length).min(MAX_ADDRESS + 1);
+        for row in (start..end).step_by(DUMP_WIDTH) {
+            let values = (row..row.saturating_add(DUMP_WIDTH).min(end))
+                .map(|address| format!(" {:>8}", self.vm.memory.get(address)))
+                .collect::<String>();
+            dump.push_str(&format!("{:>6}:{}\n", row, values));
+        }
+        dump
+    }
+
+    /// The next `count` instructions from the instruction pointer
+    pub fn list(&self, count: usize) -> String {
+        let mut listing = String::new();
+        let mut address = self.vm.ip;
+        for _ in 0..count {
+            let marker = if address == self.vm.ip { "=>" } else { "  " };
+            match Instruction::decode(address, |a| self.vm.memory.get(a)) {
+                Ok(instruction) => {
+                    listing.push_str(&format!("{} {:>6}: {}\n", marker, address, instruction));
+                    address += instruction.size();
+                }
+                Err(_) => {
+                    let value = self.vm.memory.get(address);
+                    listing.push_str(&format!("{} {:>6}: db {}\n", marker, address, value));
+                    address += 1;
+                }
+            }
+        }
+        listing
+    }
+
+    /// Run one REPL command, returning what to print, or `None` to quit
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let number = |n: usize| words.get(n).and_then(|word| word.parse::<usize>().ok());
+        let breakpoint = || {
+            let word = words.get(1)?;
+            word.parse()
+                .ok()
+                .map(Breakpoint::Address)
+                .or_else(|| Opcode::from_mnemonic(word).map(Breakpoint::Opcode))
+        };
+
+        let output = match words.first().copied().unwrap_or("") {
+            "s" | "step" => self.step(number(1).unwrap_or(1)).to_string() + "\n" + &self.list(1),
+            "c" | "continue" => self.run().to_string() + "\n" + &self.list(1),
+            "b" | "break" => match breakpoint() {
+                Some(breakpoint) => {
+                    self.add_breakpoint(breakpoint);
+                    format!("Breaking on {}\n", breakpoint)
+                }
+                None => "Usage: break <address|mnemonic>\n".to_string(),
+            },
+            "d" | "delete" => match breakpoint() {
+                Some(breakpoint) if self.remove_breakpoint(&breakpoint) => {
+                    format!("Removed breakpoint on {}\n", breakpoint)
+                }
+                _ => "No such breakpoint\n".to_string(),
+            },
+            "w" | "watch" => match number(1) {
+                Some(address) => {
+                    self.watch(address);
+                    format!("Watching address {}\n", address)
+                }
+                None => "Usage: watch <address>\n".to_string(),
+            },
+            "u" | "unwatch" => match number(1) {
+                Some(address) if self.unwatch(address) => format!("Unwatched {}\n", address),
+                _ => "No such watchpoint\n".to_string(),
+            },
+            "r" | "regs" => self.registers() + "\n",
+            "x" | "dump" => match number(1) {
+                Some(start) => self.dump(start, number(2).unwrap_or(DUMP_WIDTH)),
+                None => "Usage: dump <address> [length]\n".to_string(),
+            },
+            "l" | "list" => self.list(number(1).unwrap_or(LIST_LENGTH)),
+            "i" | "input" => {
+                let values = words[1..]
+                    .iter()
+                    .map(|word| word.parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>();
+                match values {
+                    Ok(values) => {
+                        self.vm.input.extend(values);
+                        self.registers() + "\n"
+                    }
+                    Err(_) => "Usage: input <value>...\n".to_string(),
+                }
+            }
+            "q" | "quit" => return None,
+            "" => String::new(),
+            _ => HELP.to_string(),
+        };
+        Some(output)
+    }
+
+    /// Read commands until `quit` or the end of input
+    pub fn repl<R, W>(&mut self, input: R, mut output: W) -> io::Result<()>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        write!(output, "{}(intcode) ", self.list(1))?;
+        output.flush()?;
+        for line in input.lines() {
+            match self.command(&line?) {
+                Some(text) => write!(output, "{}(intcode) ", text)?,
+                None => break,
+            }
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  s, step [n]                  execute n instructions (default 1)
+  c, continue                  run until a breakpoint, watchpoint or halt
+  b, break <address|mnemonic>  break before an address or opcode
+  d, delete <address|mnemonic> remove a breakpoint
+  w, watch <address>           stop when a memory cell changes
+  u, unwatch <address>         remove a watchpoint
+  r, regs                      show ip, relative base and I/O
+  x, dump <address> [length]   show memory
+  l, list [n]                  show the next n instructions
+  i, input <value>...          queue values for input instructions
+  q, quit
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    fn countdown() -> Debugger {
+        let program = assemble(
+            "
+                    in [n]
+            loop:   out [n]
+                    add [n], -1, [n]
+                    jnz [n], loop
+                    hlt
+            n:      db 0
+            ",
+        )
+        .unwrap();
+        Debugger::new(Vm::new(program).with_input(vec![3]))
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = countdown();
+        debugger.add_breakpoint(Breakpoint::Opcode(Opcode::Output));
+        assert_eq!(
+            debugger.run(),
+            Stop::Breakpoint(Breakpoint::Opcode(Opcode::Output))
+        );
+        assert_eq!(debugger.vm.output, vec![]);
+        assert_eq!(
+            debugger.run(),
+            Stop::Breakpoint(Breakpoint::Opcode(Opcode::Output))
+        );
+        assert_eq!(debugger.vm.output, vec![3]);
+
+        debugger.remove_breakpoint(&Breakpoint::Opcode(Opcode::Output));
+        debugger.add_breakpoint(Breakpoint::Address(11));
+        assert_eq!(debugger.run(), Stop::Breakpoint(Breakpoint::Address(11)));
+        assert_eq!(debugger.vm.output, vec![3, 2, 1]);
+        assert_eq!(debugger.run(), Stop::Halted);
+    }
+
+    #[test]
+    fn watchpoints_and_steps() {
+        let mut debugger = countdown();
+        debugger.watch(12);
+        assert_eq!(
+            debugger.run(),
+            Stop::Watchpoint {
+                address: 12,
+                old: 0,
+                new: 3
+            }
+        );
+        assert_eq!(
+            debugger.step(2),
+            Stop::Watchpoint {
+                address: 12,
+                old: 3,
+                new: 2
+            }
+        );
+        assert_eq!(debugger.step(1), Stop::Stepped);
+        assert_eq!(debugger.vm.ip, 2);
+    }
+
+    #[test]
+    fn repl_commands() {
+        let mut debugger = countdown();
+        let mut output = Vec::new();
+        let commands = "b out\nc\nr\nx 10 3\nl 2\nq\nc\n";
+        debugger.repl(commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Breaking on opcode out"));
+        assert!(output.contains("ip=2 rb=0 input=[] output=[]"));
+        assert!(output.contains("    10:        2       99        3\n"));
+        assert!(output.contains("=>      2: out [12]\n        4: add [12], -1, [12]\n"));
+        // Nothing runs after quitting
+        assert_eq!(debugger.vm.output, vec![]);
+
+        assert_eq!(
+            debugger.command("x 18446744073709551615 8"),
+            Some(String::new())
+        );
+        let last = debugger.command(&format!("x {} 100", MAX_ADDRESS - 1));
+        assert_eq!(last.unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,494 @@
+//! Checking the VM against a reference interpreter on generated programs.
+//!
+//! The reference is written straight from the puzzle text, with none of the
+//! VM's decoding, paging or caching, so the two only agree if the VM gets
+//! those right. Programs are random but mostly well formed, with the odd
+//! corrupted cell to reach the error paths. When the two disagree, the
+//! program and input are shrunk to a small reproducer.
+use crate::instruction::{Mode, Opcode};
+use crate::{Vm, VmError, MAX_ADDRESS};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+/// Generated programs can loop forever, so both sides stop after this many
+/// instructions
+pub const STEP_LIMIT: usize = 500;
+
+/// A xorshift generator, so runs can be repeated from a seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift can't leave
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Somewhere in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Somewhere in `start..end`
+    pub fn between(&mut self, start: i64, end: i64) -> i64 {
+        start + self.below((end - start) as usize) as i64
+    }
+
+    /// True about one time in `n`
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
+
+/// A random program and input for it
+pub fn generate(rng: &mut Rng) -> (Vec<i64>, Vec<i64>) {
+    // Choose opcodes and modes first, so jumps know where instructions start.
+    // Only the last instruction halts.
+    let opcodes = Opcode::ALL
+        .iter()
+        .copied()
+        .filter(|opcode| *opcode != Opcode::Halt)
+        .collect::<Vec<_>>();
+    let mut shapes = Vec::new();
+    for _ in 0..rng.between(1, 13) {
+        let opcode = opcodes[rng.below(opcodes.len())];
+        let modes = (0..opcode.arity())
+            .map(|n| match rng.below(3) {
+                // Keeping writes well formed
+                1 if opcode.writes() == Some(n) => Mode::Position,
+                digit => Mode::from_digit(digit as i64).expect("Modes are 0 to 2"),
+            })
+            .collect::<Vec<_>>();
+        shapes.push((opcode, modes));
+    }
+    shapes.push((Opcode::Halt, vec![]));
+    let mut starts = Vec::new();
+    let mut code_size = 0;
+    for (opcode, _) in &shapes {
+        starts.push(code_size as i64);
+        code_size += 1 + opcode.arity();
+    }
+    let size = code_size as i64 + rng.between(2, 10);
+
+    let mut program = Vec::new();
+    for (opcode, modes) in shapes {
+        let mut encoded = opcode.code();
+        for (n, mode) in modes.iter().enumerate() {
+            encoded += mode.digit() * 10i64.pow(n as u32 + 2);
+        }
+        program.push(encoded);
+        for (n, mode) in modes.iter().enumerate() {
+            let is_target = n == 1 && matches!(opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse);
+            program.push(match mode {
+                Mode::Immediate if is_target => starts[rng.below(starts.len())],
+                Mode::Immediate => rng.between(-3, 40),
+                Mode::Position => rng.between(0, size + 4),
+                Mode::Relative => rng.between(-6, size),
+            });
+        }
+    }
+    while (program.len() as i64) < size {
+        program.push(if rng.one_in(2) {
+            starts[rng.below(starts.len())]
+        } else {
+            rng.between(-3, 40)
+        });
+    }
+    if rng.one_in(8) {
+        let cell = rng.below(program.len());
+        program[cell] = rng.between(-200, 30000);
+    }
+
+    let input = (0..rng.below(5)).map(|_| rng.between(-3, 20)).collect();
+    (program, input)
+}
+
+/// How a run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ending {
+    Halted,
+    /// Still going after `STEP_LIMIT` instructions
+    Running,
+    Failed(VmError),
+}
+
+/// Everything a run can be compared on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub ending: Ending,
+    pub ip: usize,
+    pub relative_base: i64,
+    pub output: Vec<i64>,
+    pub memory: Vec<i64>,
+}
+
+/// The reference interpreter. Memory is a sparse map, so nothing is shared
+/// with how the VM lays it out.
+struct Reference {
+    memory: BTreeMap<usize, i64>,
+    len: usize,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    output: Vec<i64>,
+}
+
+impl Reference {
+    fn get(&self, address: usize) -> i64 {
+        self.memory.get(&address).copied().unwrap_or(0)
+    }
+
+    fn address(&self, mode: i64, value: i64) -> Result<usize, VmError> {
+        let address = if mode == 2 {
+            self.relative_base
+                .checked_add(value)
+                .ok_or(VmError::Overflow { address: self.ip })?
+        } else {
+            value
+        };
+        if address < 0 || address > MAX_ADDRESS as i64 {
+            return Err(VmError::InvalidAddress { address });
+        }
+        Ok(address as usize)
+    }
+
+    /// Execute one instruction, returning true once halted. Nothing changes
+    /// if it fails.
+    fn step(&mut self) -> Result<bool, VmError> {
+        let ip = self.ip;
+        let value = self.get(ip);
+        let invalid = VmError::InvalidOpcode { address: ip, value };
+        if value < 0 {
+            return Err(invalid);
+        }
+        let code = value % 100;
+        let (arity, writes) = match code {
+            1 | 2 | 7 | 8 => (3, Some(2)),
+            3 => (1, Some(0)),
+            4 | 9 => (1, None),
+            5 | 6 => (2, None),
+            99 => (0, None),
+            _ => return Err(invalid),
+        };
+        let mut modes = value / 100;
+        let mut parameters = Vec::new();
+        for n in 0..arity {
+            if modes % 10 > 2 {
+                return Err(VmError::InvalidMode {
+                    address: ip,
+                    value: modes % 10,
+                });
+            }
+            parameters.push((modes % 10, self.get(ip + 1 + n)));
+            modes /= 10;
+        }
+        if modes != 0 {
+            return Err(VmError::InvalidMode {
+                address: ip,
+                value: modes,
+            });
+        }
+        if writes.is_some_and(|n: usize| parameters[n].0 == 1) {
+            return Err(VmError::WriteToImmediate { address: ip });
+        }
+
+        let read = |n: usize| -> Result<i64, VmError> {
+            let (mode, value) = parameters[n];
+            if mode == 1 {
+                Ok(value)
+            } else {
+                Ok(self.get(self.address(mode, value)?))
+            }
+        };
+        let overflow = VmError::Overflow { address: ip };
+        let mut next = ip + 1 + arity;
+        let mut written = None;
+        match code {
+            1 => written = Some(read(0)?.checked_add(read(1)?).ok_or(overflow)?),
+            2 => written = Some(read(0)?.checked_mul(read(1)?).ok_or(overflow)?),
+            7 => written = Some((read(0)? < read(1)?) as i64),
+            8 => written = Some((read(0)? == read(1)?) as i64),
+            3 => {
+                written = Some(
+                    *self
+                        .input
+                        .front()
+                        .ok_or(VmError::MissingInput { address: ip })?,
+                )
+            }
+            4 => {
+                let value = read(0)?;
+                self.output.push(value);
+            }
+            5 | 6 => {
+                if (read(0)? != 0) == (code == 5) {
+                    next = self.address(0, read(1)?)?;
+                }
+            }
+            9 => self.relative_base = self.relative_base.checked_add(read(0)?).ok_or(overflow)?,
+            _ => return Ok(true),
+        }
+        if let (Some(n), Some(value)) = (writes, written) {
+            let (mode, parameter) = parameters[n];
+            let address = self.address(mode, parameter)?;
+            if code == 3 {
+                self.input.pop_front();
+            }
+            self.memory.insert(address, value);
+            self.len = self.len.max(address + 1);
+        }
+        self.ip = next;
+        Ok(false)
+    }
+}
+
+/// Run `program` on the reference interpreter
+pub fn reference(program: &[i64], input: &[i64]) -> Outcome {
+    let mut machine = Reference {
+        memory: program.iter().copied().enumerate().collect(),
+        len: program.len(),
+        ip: 0,
+        relative_base: 0,
+        input: input.iter().copied().collect(),
+        output: Vec::new(),
+    };
+    let mut ending = Ending::Running;
+    for _ in 0..STEP_LIMIT {
+        match machine.step() {
+            Ok(false) => {}
+            Ok(true) => {
+                ending = Ending::Halted;
+                break;
+            }
+            Err(error) => {
+                ending = Ending::Failed(error);
+                break;
+            }
+        }
+    }
+    Outcome {
+        ending,
+        ip: machine.ip,
+        relative_base: machine.relative_base,
+        memory: (0..machine.len)
+            .map(|address| machine.get(address))
+            .collect(),
+        output: machine.output,
+    }
+}
+
+/// Run `program` on the VM
+pub fn optimized(program: &[i64], input: &[i64]) -> Outcome {
+    let mut vm = Vm::new(program.to_vec()).with_input(input.iter().copied());
+    let mut ending = Ending::Running;
+    for _ in 0..STEP_LIMIT {
+        if let Err(error) = vm.step() {
+            ending = Ending::Failed(error);
+            break;
+        }
+        if vm.halted() {
+            ending = Ending::Halted;
+            break;
+        }
+    }
+    Outcome {
+        ending,
+        ip: vm.ip,
+        relative_base: vm.relative_base,
+        memory: vm.memory.to_vec(),
+        output: vm.output,
+    }
+}
+
+/// A program the VM and the reference disagree on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub program: Vec<i64>,
+    pub input: Vec<i64>,
+    pub reference: Outcome,
+    pub optimized: Outcome,
+}
+
+/// Check one program, failing with what each side did if they disagree
+pub fn check(program: &[i64], input: &[i64]) -> Result<(), Box<Divergence>> {
+    let (reference, optimized) = (reference(program, input), optimized(program, input));
+    if reference == optimized {
+        Ok(())
+    } else {
+        Err(Box::new(Divergence {
+            program: program.to_vec(),
+            input: input.to_vec(),
+            reference,
+            optimized,
+        }))
+    }
+}
+
+/// Shrink a failing program and input while `fails` still holds: dropping
+/// runs of cells, then simplifying single cells, then dropping inputs, until
+/// none of those help
+pub fn minimize<F>(program: &[i64], input: &[i64], fails: F) -> (Vec<i64>, Vec<i64>)
+where
+    F: Fn(&[i64], &[i64]) -> bool,
+{
+    let (mut program, mut input) = (program.to_vec(), input.to_vec());
+    let mut shrinking = true;
+    while shrinking {
+        shrinking = false;
+        let mut run = program.len() / 2;
+        while run > 0 {
+            let mut start = 0;
+            while start + run <= program.len() {
+                let mut candidate = program.clone();
+                candidate.drain(start..start + run);
+                if fails(&candidate, &input) {
+                    program = candidate;
+                    shrinking = true;
+                } else {
+                    start += 1;
+                }
+            }
+            run /= 2;
+        }
+        for cell in 0..program.len() {
+            // Zero, or the opcode without its modes
+            for simpler in [0, program[cell] % 100] {
+                if simpler.unsigned_abs() < program[cell].unsigned_abs() {
+                    let mut candidate = program.clone();
+                    candidate[cell] = simpler;
+                    if fails(&candidate, &input) {
+                        program = candidate;
+                        shrinking = true;
+                        break;
+                    }
+                }
+            }
+        }
+        let mut n = 0;
+        while n < input.len() {
+            let mut candidate = input.clone();
+            candidate.remove(n);
+            if fails(&program, &candidate) {
+                input = candidate;
+                shrinking = true;
+            } else {
+                n += 1;
+            }
+        }
+    }
+    (program, input)
+}
+
+/// Check `cases` generated programs, starting from `seed`, handing back a
+/// minimized reproducer for the first disagreement
+pub fn fuzz(seed: u64, cases: usize) -> Result<(), Box<Divergence>> {
+    let mut rng = Rng::new(seed);
+    for _ in 0..cases {
+        let (program, input) = generate(&mut rng);
+        if check(&program, &input).is_err() {
+            let (program, input) = minimize(&program, &input, |program, input| {
+                check(program, input).is_err()
+            });
+            return check(&program, &input);
+        }
+    }
+    Ok(())
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ending::Halted => write!(f, "halted"),
+            Ending::Running => write!(f, "still running after {} steps", STEP_LIMIT),
+            Ending::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  {}", self.ending)?;
+        writeln!(f, "  ip {}, rb {}", self.ip, self.relative_base)?;
+        writeln!(f, "  output: {}", join(&self.output))?;
+        writeln!(f, "  memory: {}", join(&self.memory))
+    }
+}
+
+/// The reproducer, then what each side made of it
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "program: {}", join(&self.program))?;
+        writeln!(f, "input: {}", join(&self.input))?;
+        writeln!(f, "reference:\n{}", self.reference)?;
+        write!(f, "vm:\n{}", self.optimized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn agrees_on_generated_programs() {
+        if let Err(divergence) = fuzz(2019, 2000) {
+            panic!("The VM disagrees with the reference\n{}", divergence);
+        }
+    }
+
+    #[test]
+    fn agrees_on_puzzle_examples() {
+        let compare = parse_program(
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+             1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+             999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        )
+        .unwrap();
+        for input in 6..10 {
+            assert_eq!(check(&compare, &[input]), Ok(()));
+        }
+        let quine = parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        assert_eq!(reference(&quine.unwrap(), &[]).output.len(), 16);
+        assert_eq!(
+            reference(&[1105, 1, -2], &[]).ending,
+            Ending::Failed(VmError::InvalidAddress { address: -2 })
+        );
+    }
+
+    #[test]
+    fn minimizes_reproducers() {
+        // Stands in for a bug: outputting 7 after reading any input
+        let fails = |program: &[i64], input: &[i64]| {
+            let outcome = reference(program, input);
+            !input.is_empty() && outcome.output.contains(&7)
+        };
+        let program = vec![1101, 3, 4, 20, 3, 21, 104, 5, 1001, 20, 0, 22, 4, 22, 99];
+        let input = vec![9, 8];
+        assert!(fails(&program, &input));
+        let (smaller, input) = minimize(&program, &input, fails);
+        assert!(fails(&smaller, &input));
+        assert!(smaller.len() < program.len());
+        assert_eq!(input.len(), 1);
+        // Nothing is left that could go on its own
+        for cell in 0..smaller.len() {
+            let mut candidate = smaller.clone();
+            candidate.remove(cell);
+            assert!(!fails(&candidate, &input));
+        }
+    }
+}
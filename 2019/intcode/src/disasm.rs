@@ -0,0 +1,275 @@
+//! Turning a program back into something readable.
+//!
+//! Intcode doesn't separate code from data, so this follows control flow from
+//! address 0 to find the instructions and treats everything it can't reach as
+//! data. Jumps through memory can't be followed, so code only reached that way
+//! shows up as data too. The listing is valid input for the assembler.
+use crate::instruction::{Instruction, Mode, Opcode};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Values per line when listing data
+const DATA_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Code(Instruction),
+    Data(Vec<i64>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: usize,
+    pub item: Item,
+}
+
+impl Line {
+    /// How many integers the line covers
+    pub fn size(&self) -> usize {
+        match &self.item {
+            Item::Code(instruction) => instruction.size(),
+            Item::Data(values) => values.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub lines: Vec<Line>,
+    /// Addresses jumped to by immediate jumps that start a line, which get
+    /// labels. Other targets are left as numbers.
+    pub jump_targets: BTreeSet<usize>,
+}
+
+/// Where control can go after `instruction` at `address`, as far as we can
+/// tell without running it
+fn successors(address: usize, instruction: &Instruction) -> Vec<usize> {
+    let next = address + instruction.size();
+    match instruction.opcode {
+        Opcode::Halt => vec![],
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let (condition, target) = (instruction.parameters()[0], instruction.parameters()[1]);
+            let mut successors = Vec::new();
+            let jumps_if = instruction.opcode == Opcode::JumpIfTrue;
+            let always = condition.mode == Mode::Immediate && (condition.value != 0) == jumps_if;
+            let never = condition.mode == Mode::Immediate && (condition.value != 0) != jumps_if;
+            if target.mode == Mode::Immediate && target.value >= 0 && !never {
+                successors.push(target.value as usize);
+            }
+            if !always {
+                successors.push(next);
+            }
+            successors
+        }
+        _ => vec![next],
+    }
+}
+
+/// The immediate target of a jump, if it has one
+fn jump_target(instruction: &Instruction) -> Option<usize> {
+    match instruction.opcode {
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let target = instruction.parameters()[1];
+            if target.mode == Mode::Immediate && target.value >= 0 {
+                Some(target.value as usize)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn disassemble(program: &[i64]) -> Listing {
+    // Which instruction, if any, starts at each address
+    let mut code: Vec<Option<Instruction>> = vec![None; program.len()];
+    // Cells covered by an instruction, so we don't decode overlapping ones
+    let mut covered = vec![false; program.len()];
+    let mut jump_targets = BTreeSet::new();
+
+    let mut pending = vec![0];
+    while let Some(address) = pending.pop() {
+        if address >= program.len() || covered[address] {
+            continue;
+        }
+        let instruction = match Instruction::decode_slice(program, address) {
+            Ok(instruction) => instruction,
+            Err(_) => continue,
+        };
+        let end = address + instruction.size();
+        if end > program.len() || covered[address..end].iter().any(|c| *c) {
+            continue;
+        }
+        covered[address..end].iter_mut().for_each(|c| *c = true);
+        code[address] = Some(instruction);
+        if let Some(target) = jump_target(&instruction) {
+            jump_targets.insert(target);
+        }
+        pending.extend(successors(address, &instruction));
+    }
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        if let Some(instruction) = code[address] {
+            lines.push(Line {
+                address,
+                item: Item::Code(instruction),
+            });
+            address += instruction.size();
+            continue;
+        }
+        // Data runs break at jump targets so they can still get a label
+        let continues_run = lines.last().is_some_and(|line| match &line.item {
+            Item::Data(values) => values.len() < DATA_WIDTH && !jump_targets.contains(&address),
+            Item::Code(_) => false,
+        });
+        if continues_run {
+            if let Some(Line {
+                item: Item::Data(values),
+                ..
+            }) = lines.last_mut()
+            {
+                values.push(program[address]);
+            }
+        } else {
+            lines.push(Line {
+                address,
+                item: Item::Data(vec![program[address]]),
+            });
+        }
+        address += 1;
+    }
+
+    // Targets past the end or inside an instruction have no line to label
+    let starts = lines
+        .iter()
+        .map(|line| line.address)
+        .collect::<BTreeSet<_>>();
+    jump_targets.retain(|target| starts.contains(target));
+    Listing {
+        lines,
+        jump_targets,
+    }
+}
+
+pub fn label(address: usize) -> String {
+    format!("L{}", address)
+}
+
+impl Listing {
+    /// The instruction's text, with immediate jump targets named by label
+    fn instruction_text(&self, instruction: &Instruction) -> String {
+        match jump_target(instruction) {
+            Some(target) if self.jump_targets.contains(&target) => format!(
+                "{} {}, {}",
+                instruction.opcode.mnemonic(),
+                instruction.parameters()[0],
+                label(target)
+            ),
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+/// One line per instruction or run of data, with the address and raw
+/// integers in a trailing comment
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            if self.jump_targets.contains(&line.address) {
+                writeln!(f, "{}:", label(line.address))?;
+            }
+            match &line.item {
+                Item::Code(instruction) => {
+                    let raw = instruction
+                        .encode()
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>();
+                    writeln!(
+                        f,
+                        "    {:<32}; {:>5}: {}",
+                        self.instruction_text(instruction),
+                        line.address,
+                        raw.join(" ")
+                    )?;
+                }
+                Item::Data(values) => {
+                    let values = values
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>();
+                    let text = format!("db {}", values.join(", "));
+                    writeln!(f, "    {:<32}; {:>5}", text, line.address)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_program};
+
+    #[test]
+    fn separates_code_from_data() {
+        let listing = disassemble(&[1002, 4, 3, 4, 33]);
+        assert_eq!(
+            listing.to_string(),
+            "    mul [4], 3, [4]                 ;     0: 1002 4 3 4\n\
+             \x20   db 33                           ;     4\n"
+        );
+
+        // Reads a number, halts, and has a constant and scratch cell after
+        let listing = disassemble(&[3, 5, 4, 5, 99, 0, 7]);
+        let items = listing
+            .lines
+            .iter()
+            .map(|line| (line.address, line.item.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items[2],
+            (4, Item::Code(Instruction::decode_slice(&[99], 0).unwrap()))
+        );
+        assert_eq!(items[3], (5, Item::Data(vec![0, 7])));
+    }
+
+    #[test]
+    fn labels_jump_targets() {
+        // Day 5's "is the input 0" program, which skips over data with jumps
+        let program = parse_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap();
+        let listing = disassemble(&program);
+        // The jump goes through memory, so only the fall through is followed
+        assert!(listing.jump_targets.is_empty());
+        assert_eq!(
+            listing.lines.last(),
+            Some(&Line {
+                address: 12,
+                item: Item::Data(vec![-1, 0, 1, 9])
+            })
+        );
+
+        let program = vec![1105, 1, 4, 42, 4, 3, 99];
+        let listing = disassemble(&program);
+        assert_eq!(listing.jump_targets, vec![4].into_iter().collect());
+        let text = listing.to_string();
+        assert!(text.contains("jnz 1, L4"));
+        assert!(text.contains("db 42"));
+        assert!(text.contains("L4:\n    out [3]"));
+    }
+
+    #[test]
+    fn reassembles_jumps_without_lines() {
+        // Past the end of the program, then into the middle of the jump
+        for program in [vec![1105, 1, 100, 99], vec![1105, 1, 1, 99]] {
+            let listing = disassemble(&program);
+            assert!(listing.jump_targets.is_empty());
+            assert_eq!(assemble(&listing.to_string()), Ok(program));
+        }
+        let program = vec![1105, 1, 4, 42, 4, 3, 99];
+        assert_eq!(assemble(&disassemble(&program).to_string()), Ok(program));
+    }
+}
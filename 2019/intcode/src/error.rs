@@ -0,0 +1,54 @@
+use std::fmt;
+
+/// Everything that can go wrong loading or running an Intcode program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    /// The value at `address` isn't a known opcode
+    InvalidOpcode { address: usize, value: i64 },
+    /// The instruction at `address` asks for a parameter mode that doesn't exist
+    InvalidMode { address: usize, value: i64 },
+    /// The instruction at `address` tried to write through an immediate parameter
+    WriteToImmediate { address: usize },
+    /// A parameter pointed at a negative address, or past `MAX_ADDRESS`
+    InvalidAddress { address: i64 },
+    /// The instruction at `address` wanted input but there wasn't any
+    MissingInput { address: usize },
+    /// Arithmetic at `address` overflowed an i64
+    Overflow { address: usize },
+    /// The program text had something other than an integer at `position`
+    Parse { position: usize, text: String },
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::InvalidOpcode { address, value } => {
+                write!(f, "Invalid opcode {} at address {}", value, address)
+            }
+            VmError::InvalidMode { address, value } => {
+                write!(f, "Invalid parameter mode {} at address {}", value, address)
+            }
+            VmError::WriteToImmediate { address } => write!(
+                f,
+                "Instruction at address {} writes to an immediate parameter",
+                address
+            ),
+            VmError::InvalidAddress { address } => write!(f, "Address {} is out of range", address),
+            VmError::MissingInput { address } => {
+                write!(f, "Instruction at address {} is waiting on input", address)
+            }
+            VmError::Overflow { address } => {
+                write!(f, "Instruction at address {} overflowed", address)
+            }
+            VmError::Parse { position, text } => {
+                write!(
+                    f,
+                    "Expected an integer at position {}, found {:?}",
+                    position, text
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
@@ -0,0 +1,324 @@
+//! Decoding the integers in memory into instructions.
+//!
+//! An instruction is an opcode in the last two digits of its first integer,
+//! with one digit per parameter above that saying how to read the parameter.
+use crate::VmError;
+use std::fmt;
+
+/// Ordered by code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Multiply,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjustRelativeBase,
+        Opcode::Halt,
+    ];
+
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        Some(match code {
+            1 => Opcode::Add,
+            2 => Opcode::Multiply,
+            3 => Opcode::Input,
+            4 => Opcode::Output,
+            5 => Opcode::JumpIfTrue,
+            6 => Opcode::JumpIfFalse,
+            7 => Opcode::LessThan,
+            8 => Opcode::Equals,
+            9 => Opcode::AdjustRelativeBase,
+            99 => Opcode::Halt,
+            _ => return None,
+        })
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Multiply => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    /// How many parameters follow the opcode
+    pub fn arity(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+
+    /// Which parameter, if any, is an address the instruction writes to
+    pub fn writes(self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => Some(2),
+            Opcode::Input => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Multiply => "mul",
+            Opcode::Input => "in",
+            Opcode::Output => "out",
+            Opcode::JumpIfTrue => "jnz",
+            Opcode::JumpIfFalse => "jz",
+            Opcode::LessThan => "lt",
+            Opcode::Equals => "eq",
+            Opcode::AdjustRelativeBase => "arb",
+            Opcode::Halt => "hlt",
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        Opcode::ALL
+            .iter()
+            .copied()
+            .find(|opcode| opcode.mnemonic() == mnemonic)
+    }
+}
+
+/// How a parameter's value is turned into an operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// The value is an address to read from or write to
+    Position,
+    /// The value is the operand itself
+    Immediate,
+    /// The value is an offset from the relative base
+    Relative,
+}
+
+impl Mode {
+    pub fn from_digit(digit: i64) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Parameter {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+/// Position mode `[12]`, immediate `12` and relative `[rb+12]`
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb{}]", self.value),
+            Mode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+const UNUSED: Parameter = Parameter {
+    mode: Mode::Position,
+    value: 0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    parameters: [Parameter; 3],
+}
+
+impl Instruction {
+    /// Build an instruction, panicking unless there's exactly one parameter
+    /// per argument of the opcode
+    pub fn new(opcode: Opcode, parameters: &[Parameter]) -> Self {
+        assert!(
+            parameters.len() == opcode.arity(),
+            "{} takes {} parameters",
+            opcode.mnemonic(),
+            opcode.arity()
+        );
+        let mut instruction = Instruction {
+            opcode,
+            parameters: [UNUSED; 3],
+        };
+        instruction.parameters[..parameters.len()].copy_from_slice(parameters);
+        instruction
+    }
+
+    /// Decode the instruction at `address`, reading memory through `fetch`
+    pub fn decode<F>(address: usize, fetch: F) -> Result<Self, VmError>
+    where
+        F: Fn(usize) -> i64,
+    {
+        let value = fetch(address);
+        let opcode = Opcode::from_code(value % 100)
+            .filter(|_| value >= 0)
+            .ok_or(VmError::InvalidOpcode { address, value })?;
+
+        let mut instruction = Instruction {
+            opcode,
+            parameters: [UNUSED; 3],
+        };
+        let mut modes = value / 100;
+        for (offset, parameter) in instruction.parameters[..opcode.arity()]
+            .iter_mut()
+            .enumerate()
+        {
+            parameter.mode = Mode::from_digit(modes % 10).ok_or(VmError::InvalidMode {
+                address,
+                value: modes % 10,
+            })?;
+            parameter.value = fetch(address + 1 + offset);
+            modes /= 10;
+        }
+        if modes != 0 {
+            return Err(VmError::InvalidMode {
+                address,
+                value: modes,
+            });
+        }
+        if let Some(written) = opcode.writes() {
+            if instruction.parameters[written].mode == Mode::Immediate {
+                return Err(VmError::WriteToImmediate { address });
+            }
+        }
+        Ok(instruction)
+    }
+
+    /// Decode the instruction at `address` of a program in a slice
+    pub fn decode_slice(program: &[i64], address: usize) -> Result<Self, VmError> {
+        Instruction::decode(address, |address| {
+            program.get(address).copied().unwrap_or(0)
+        })
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters[..self.opcode.arity()]
+    }
+
+    /// How many integers the instruction takes up in memory
+    pub fn size(&self) -> usize {
+        1 + self.opcode.arity()
+    }
+
+    /// The integers this instruction is stored as
+    pub fn encode(&self) -> Vec<i64> {
+        let modes = self
+            .parameters()
+            .iter()
+            .rev()
+            .fold(0, |modes, parameter| modes * 10 + parameter.mode.digit());
+        let mut encoded = vec![modes * 100 + self.opcode.code()];
+        encoded.extend(self.parameters().iter().map(|parameter| parameter.value));
+        encoded
+    }
+}
+
+/// The mnemonic followed by its parameters, like `add [4], 3, [rb-1]`
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic())?;
+        for (n, parameter) in self.parameters().iter().enumerate() {
+            let separator = if n == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, parameter)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_modes() {
+        let instruction = Instruction::decode_slice(&[1002, 4, 3, 4, 33], 0).unwrap();
+        assert_eq!(instruction.opcode, Opcode::Multiply);
+        assert_eq!(
+            instruction.parameters(),
+            &[
+                Parameter {
+                    mode: Mode::Position,
+                    value: 4
+                },
+                Parameter {
+                    mode: Mode::Immediate,
+                    value: 3
+                },
+                Parameter {
+                    mode: Mode::Position,
+                    value: 4
+                },
+            ]
+        );
+        assert_eq!(instruction.encode(), vec![1002, 4, 3, 4]);
+        assert_eq!(instruction.to_string(), "mul [4], 3, [4]");
+        assert_eq!(
+            Instruction::decode_slice(&[204, -3], 0)
+                .unwrap()
+                .to_string(),
+            "out [rb-3]"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_instructions() {
+        assert_eq!(
+            Instruction::decode_slice(&[0, 42], 1),
+            Err(VmError::InvalidOpcode {
+                address: 1,
+                value: 42
+            })
+        );
+        assert_eq!(
+            Instruction::decode_slice(&[301, 0, 0, 0], 0),
+            Err(VmError::InvalidMode {
+                address: 0,
+                value: 3
+            })
+        );
+        assert_eq!(
+            Instruction::decode_slice(&[10001, 0, 0, 0], 0),
+            Err(VmError::WriteToImmediate { address: 0 })
+        );
+    }
+}
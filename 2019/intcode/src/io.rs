@@ -0,0 +1,135 @@
+//! Plugging a VM's input and output into queues, channels, or other VMs.
+use crate::vm::State;
+use crate::{Vm, VmError};
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Somewhere input comes from
+pub trait Source {
+    /// The next value, or `None` if there isn't one to give
+    fn read(&mut self) -> Option<i64>;
+}
+
+/// Somewhere output goes to
+pub trait Sink {
+    fn write(&mut self, value: i64);
+}
+
+impl Source for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+/// Blocks until a value arrives, or the sender hangs up
+impl Source for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+impl Sink for Vec<i64> {
+    fn write(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+impl Sink for VecDeque<i64> {
+    fn write(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+/// Values sent after the receiver hangs up are dropped
+impl Sink for Sender<i64> {
+    fn write(&mut self, value: i64) {
+        let _ = self.send(value);
+    }
+}
+
+impl Vm {
+    /// Run until the program halts, or wants input `source` doesn't have
+    pub fn run_with<S, K>(&mut self, source: &mut S, sink: &mut K) -> Result<State, VmError>
+    where
+        S: Source,
+        K: Sink,
+    {
+        loop {
+            match self.resume()? {
+                State::Output(value) => sink.write(value),
+                State::NeedsInput => match source.read() {
+                    Some(value) => self.input.push_back(value),
+                    None => return Ok(State::NeedsInput),
+                },
+                State::Halted => return Ok(State::Halted),
+            }
+        }
+    }
+
+    /// Run on a thread of its own until it halts, handing back the finished
+    /// VM. Fails with `MissingInput` if `input` hangs up while it's waiting.
+    pub fn spawn(
+        mut self,
+        mut input: Receiver<i64>,
+        mut output: Sender<i64>,
+    ) -> JoinHandle<Result<Vm, VmError>> {
+        thread::spawn(move || match self.run_with(&mut input, &mut output)? {
+            State::Halted => Ok(self),
+            _ => Err(VmError::MissingInput { address: self.ip }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn queues() {
+        let mut vm = Vm::new(vec![3, 9, 3, 10, 4, 10, 4, 9, 99, 0, 0]);
+        let mut input = VecDeque::from(vec![1]);
+        let mut output = Vec::new();
+        assert_eq!(vm.run_with(&mut input, &mut output), Ok(State::NeedsInput));
+        input.push_back(2);
+        assert_eq!(vm.run_with(&mut input, &mut output), Ok(State::Halted));
+        assert_eq!(output, vec![2, 1]);
+    }
+
+    #[test]
+    fn amplifiers_on_threads() {
+        // Day 7's feedback loop, with the last amplifier talking to us
+        let program = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,\
+             4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        )
+        .unwrap();
+        let phases = [9, 8, 7, 6, 5];
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..=phases.len()).map(|_| channel()).unzip();
+        for (phase, sender) in phases.iter().zip(&senders) {
+            sender.send(*phase).unwrap();
+        }
+        senders[0].send(0).unwrap();
+
+        let mut receivers = receivers.into_iter();
+        let amplifiers = senders[1..]
+            .iter()
+            .map(|sender| Vm::new(program.clone()).spawn(receivers.next().unwrap(), sender.clone()))
+            .collect::<Vec<_>>();
+        let output = receivers.next().unwrap();
+        let first = senders[0].clone();
+        drop(senders);
+
+        let mut last = None;
+        for value in output.iter() {
+            last = Some(value);
+            first.send(value).unwrap_or_default();
+        }
+        for amplifier in amplifiers {
+            assert!(amplifier.join().unwrap().unwrap().halted());
+        }
+        assert_eq!(last, Some(139629729));
+    }
+}
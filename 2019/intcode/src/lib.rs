@@ -0,0 +1,63 @@
+//! An Intcode virtual machine, shared by every 2019 day that runs Intcode.
+pub mod ascii;
+pub mod asm;
+pub mod debugger;
+pub mod differential;
+pub mod disasm;
+pub mod error;
+pub mod instruction;
+pub mod io;
+pub mod memory;
+pub mod network;
+pub mod profile;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+pub mod vm;
+
+pub use ascii::Ascii;
+pub use asm::{assemble, AsmError};
+pub use debugger::{Breakpoint, Debugger, Stop};
+pub use disasm::{disassemble, Listing};
+pub use error::VmError;
+pub use instruction::{Instruction, Mode, Opcode, Parameter};
+pub use io::{Sink, Source};
+pub use memory::{Memory, MAX_ADDRESS};
+pub use network::{Network, Topology};
+pub use profile::Profile;
+pub use snapshot::Snapshot;
+pub use symbolic::{evaluate_symbolic, Linear, SymbolicError};
+pub use trace::{TraceError, Tracer};
+pub use vm::{run, Observer, State, Vm};
+
+/// Parse comma separated integers, allowing whitespace and a trailing newline
+pub fn parse_program(text: &str) -> Result<Vec<i64>, VmError> {
+    text.trim()
+        .split(',')
+        .enumerate()
+        .map(|(position, value)| {
+            let value = value.trim();
+            value.parse().map_err(|_| VmError::Parse {
+                position,
+                text: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_programs() {
+        assert_eq!(parse_program("1,0, 0,0,\n99\n"), Ok(vec![1, 0, 0, 0, 99]));
+        assert_eq!(
+            parse_program("1,x,99"),
+            Err(VmError::Parse {
+                position: 1,
+                text: "x".to_string()
+            })
+        );
+    }
+}
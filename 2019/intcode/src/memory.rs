@@ -0,0 +1,205 @@
+use crate::instruction::Instruction;
+use crate::VmError;
+use std::sync::Arc;
+
+/// The highest address a program may touch. Memory grows on demand, so this
+/// stops a stray pointer from allocating the whole machine.
+pub const MAX_ADDRESS: usize = 1 << 24;
+
+/// Memory is split into pages of this many cells, which are shared between
+/// clones until one of them writes to it
+const PAGE_SIZE: usize = 64;
+
+type Page = Arc<[i64; PAGE_SIZE]>;
+
+/// The instructions decoded at a page's worth of addresses, shared between
+/// clones the same way
+type DecodedPage = Arc<[Option<Instruction>; PAGE_SIZE]>;
+
+/// Intcode memory: the program, followed by as many zeroes as it needs.
+///
+/// Cloning is cheap, since clones share pages and only copy the ones they
+/// write to.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    pages: Vec<Page>,
+    /// One past the highest address written or loaded
+    len: usize,
+    /// Instructions already decoded, by address. Writing to any cell an
+    /// instruction covers throws it away.
+    decoded: Vec<DecodedPage>,
+}
+
+/// Only the contents count, not what's been decoded or shared
+impl PartialEq for Memory {
+    fn eq(&self, other: &Memory) -> bool {
+        self.len == other.len && self.pages == other.pages
+    }
+}
+
+impl Eq for Memory {}
+
+impl Memory {
+    pub fn new(program: Vec<i64>) -> Self {
+        let pages = program
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+        Memory {
+            pages,
+            len: program.len(),
+            decoded: Vec::new(),
+        }
+    }
+
+    /// Turn a value from the program into an address
+    pub fn address(value: i64) -> Result<usize, VmError> {
+        if value < 0 || value as u64 > MAX_ADDRESS as u64 {
+            Err(VmError::InvalidAddress { address: value })
+        } else {
+            Ok(value as usize)
+        }
+    }
+
+    /// Memory that hasn't been written yet reads as 0
+    pub fn get(&self, address: usize) -> i64 {
+        self.pages
+            .get(address / PAGE_SIZE)
+            .map_or(0, |page| page[address % PAGE_SIZE])
+    }
+
+    pub fn set(&mut self, address: usize, value: i64) -> Result<(), VmError> {
+        if address > MAX_ADDRESS {
+            return Err(VmError::InvalidAddress {
+                address: address as i64,
+            });
+        }
+        let page = address / PAGE_SIZE;
+        if page >= self.pages.len() {
+            self.pages.resize(page + 1, Arc::new([0; PAGE_SIZE]));
+        }
+        Arc::make_mut(&mut self.pages[page])[address % PAGE_SIZE] = value;
+        self.len = self.len.max(address + 1);
+        // The longest instruction is 4 cells, so only the 3 before it can
+        // cover this one
+        for covering in address.saturating_sub(3)..=address {
+            if let Some(page) = self.decoded.get_mut(covering / PAGE_SIZE) {
+                if page[covering % PAGE_SIZE].is_some() {
+                    Arc::make_mut(page)[covering % PAGE_SIZE] = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the instruction at `address`, reusing an earlier decode if
+    /// nothing it covers has been written since
+    pub fn instruction(&mut self, address: usize) -> Result<Instruction, VmError> {
+        if let Some(Some(instruction)) = self
+            .decoded
+            .get(address / PAGE_SIZE)
+            .map(|page| page[address % PAGE_SIZE])
+        {
+            return Ok(instruction);
+        }
+        let instruction = Instruction::decode(address, |address| self.get(address))?;
+        // Only cache within the program and what it's written, so a wild
+        // jump doesn't grow the cache to match
+        if address < self.len {
+            let page = address / PAGE_SIZE;
+            if page >= self.decoded.len() {
+                self.decoded.resize(page + 1, Arc::new([None; PAGE_SIZE]));
+            }
+            Arc::make_mut(&mut self.decoded[page])[address % PAGE_SIZE] = Some(instruction);
+        }
+        Ok(instruction)
+    }
+
+    /// How much memory has been touched so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Pages this shares with `other` rather than having its own copy of
+    pub fn shared_pages(&self, other: &Memory) -> usize {
+        self.pages
+            .iter()
+            .zip(&other.pages)
+            .filter(|(mine, theirs)| Arc::ptr_eq(mine, theirs))
+            .count()
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        (0..self.len).map(|address| self.get(address)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_and_shares_pages() {
+        let program = (0..200).collect::<Vec<i64>>();
+        let mut memory = Memory::new(program.clone());
+        assert_eq!(memory.len(), 200);
+        assert_eq!(memory.to_vec(), program);
+        assert_eq!(memory.get(1000), 0);
+
+        let original = memory.clone();
+        memory.set(1000, 7).unwrap();
+        memory.set(3, -3).unwrap();
+        assert_eq!(
+            (memory.get(1000), memory.get(3), original.get(3)),
+            (7, -3, 3)
+        );
+        assert_eq!(memory.len(), 1001);
+        // Only the page holding address 3 was copied
+        assert_eq!(memory.shared_pages(&original), 3);
+    }
+
+    #[test]
+    fn clones_of_written_memory_share_pages() {
+        let mut memory = Memory::new(vec![]);
+        for address in 0..640 {
+            memory.set(address, address as i64).unwrap();
+        }
+        let (a, b) = (memory.clone(), memory.clone());
+        assert_eq!(a.shared_pages(&b), 10);
+        assert_eq!(a.shared_pages(&memory), 10);
+    }
+
+    #[test]
+    fn clones_share_decoded_instructions() {
+        let mut memory = Memory::new(vec![1101, 2, 3, 7, 99]);
+        memory.instruction(0).unwrap();
+        let mut fork = memory.clone();
+        assert!(Arc::ptr_eq(&memory.decoded[0], &fork.decoded[0]));
+        // Reading the cache doesn't copy it, but writing does
+        assert_eq!(fork.instruction(0), memory.instruction(0));
+        assert!(Arc::ptr_eq(&memory.decoded[0], &fork.decoded[0]));
+        fork.set(3, 8).unwrap();
+        assert!(!Arc::ptr_eq(&memory.decoded[0], &fork.decoded[0]));
+        assert_eq!(memory.instruction(0).unwrap().encode(), vec![1101, 2, 3, 7]);
+    }
+
+    #[test]
+    fn writes_invalidate_decoded_instructions() {
+        let mut memory = Memory::new(vec![1101, 2, 3, 7, 99]);
+        assert_eq!(memory.instruction(0).unwrap().encode(), vec![1101, 2, 3, 7]);
+        memory.set(3, 8).unwrap();
+        assert_eq!(memory.instruction(0).unwrap().encode(), vec![1101, 2, 3, 8]);
+        // Past the end of the instruction, so it stays cached
+        memory.set(4, 1).unwrap();
+        assert_eq!(memory.instruction(0).unwrap().encode(), vec![1101, 2, 3, 8]);
+        assert_eq!(memory, Memory::new(vec![1101, 2, 3, 8, 1]));
+    }
+}
@@ -0,0 +1,160 @@
+//! Running a group of VMs that feed each other, round robin on one thread.
+use crate::vm::State;
+use crate::{Vm, VmError};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// Each machine's output goes to the input of the next, and the last
+    /// machine's output goes to the first
+    Ring,
+    /// Machines write packets of `packet_size` values, the first being the
+    /// address of the machine the rest go to
+    Mesh { packet_size: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    pub vms: Vec<Vm>,
+    pub topology: Topology,
+    /// Given to a machine waiting on an empty queue, once a round, instead of
+    /// leaving it blocked
+    pub idle_input: Option<i64>,
+    /// Packets sent to addresses outside the mesh, including the address
+    pub undelivered: Vec<Vec<i64>>,
+    /// The packet each machine is part way through writing
+    partial: Vec<Vec<i64>>,
+}
+
+impl Network {
+    pub fn new(vms: Vec<Vm>, topology: Topology) -> Self {
+        let partial = vec![Vec::new(); vms.len()];
+        Network {
+            vms,
+            topology,
+            idle_input: None,
+            undelivered: Vec::new(),
+            partial,
+        }
+    }
+
+    pub fn ring(vms: Vec<Vm>) -> Self {
+        Network::new(vms, Topology::Ring)
+    }
+
+    pub fn mesh(vms: Vec<Vm>, packet_size: usize) -> Self {
+        Network::new(vms, Topology::Mesh { packet_size })
+    }
+
+    pub fn with_idle_input(mut self, value: i64) -> Self {
+        self.idle_input = Some(value);
+        self
+    }
+
+    fn deliver(&mut self, from: usize, value: i64) {
+        match self.topology {
+            Topology::Ring => {
+                let to = (from + 1) % self.vms.len();
+                self.vms[to].input.push_back(value);
+            }
+            Topology::Mesh { packet_size } => {
+                self.partial[from].push(value);
+                if self.partial[from].len() < packet_size {
+                    return;
+                }
+                let packet = std::mem::take(&mut self.partial[from]);
+                match usize::try_from(packet[0]) {
+                    Ok(to) if to < self.vms.len() => self.vms[to].input.extend(&packet[1..]),
+                    _ => self.undelivered.push(packet),
+                }
+            }
+        }
+    }
+
+    /// Give every machine a turn, running it until it halts or runs out of
+    /// input. Returns whether anything was written.
+    pub fn round(&mut self) -> Result<bool, VmError> {
+        let mut active = false;
+        for id in 0..self.vms.len() {
+            let mut idled = false;
+            loop {
+                match self.vms[id].resume()? {
+                    State::Output(value) => {
+                        active = true;
+                        self.deliver(id, value);
+                    }
+                    State::NeedsInput => match self.idle_input {
+                        Some(value) if !idled => {
+                            idled = true;
+                            self.vms[id].input.push_back(value);
+                        }
+                        _ => break,
+                    },
+                    State::Halted => break,
+                }
+            }
+        }
+        Ok(active)
+    }
+
+    /// Run rounds until one goes by without anything being written, which
+    /// means every machine is halted or waiting on input that won't come.
+    /// Returns how many rounds that took.
+    pub fn run(&mut self) -> Result<usize, VmError> {
+        let mut rounds = 1;
+        while self.round()? {
+            rounds += 1;
+        }
+        Ok(rounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_program};
+
+    #[test]
+    fn feedback_ring() {
+        let program = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,\
+             4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        )
+        .unwrap();
+        let vms = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|phase| Vm::new(program.clone()).with_input(vec![*phase]))
+            .collect();
+        let mut network = Network::ring(vms);
+        network.vms[0].input.push_back(0);
+        network.run().unwrap();
+        assert!(network.vms.iter().all(Vm::halted));
+        assert_eq!(network.vms[0].input, vec![139629729]);
+    }
+
+    #[test]
+    fn mesh_packets() {
+        let sender = assemble("out 1\nout 42\nhlt").unwrap();
+        let forwarder = assemble(
+            "
+                    in [value]
+                    add [value], 1, [value]
+                    out 255
+                    out [value]
+                    jz 0, 0
+            value:  db 0
+            ",
+        )
+        .unwrap();
+        let mut network = Network::mesh(vec![Vm::new(sender), Vm::new(forwarder)], 2);
+        assert_eq!(network.run(), Ok(2));
+        assert_eq!(network.undelivered, vec![vec![255, 43]]);
+        assert!(!network.vms[1].halted());
+
+        // With idle input, the forwarder passes along the -1s it's given
+        let forwarder = Vm::new(network.vms[1].memory.to_vec());
+        let mut network = Network::mesh(vec![forwarder], 2).with_idle_input(-1);
+        assert_eq!(network.round(), Ok(true));
+        assert_eq!(network.undelivered, vec![vec![255, 0]]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Counting where a program spends its instructions.
+use crate::instruction::{Instruction, Opcode};
+use crate::trace::Record;
+use crate::vm::Observer;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Addresses listed in a profile's report
+const HOT_ADDRESSES: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub instructions: u64,
+    /// How often each address ran, and the instruction last seen there
+    pub addresses: HashMap<usize, (u64, Instruction)>,
+    pub opcodes: BTreeMap<Opcode, u64>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `n` most executed addresses with their counts, busiest first
+    pub fn hot_addresses(&self, n: usize) -> Vec<(usize, u64, Instruction)> {
+        let mut hot = self
+            .addresses
+            .iter()
+            .map(|(address, (count, instruction))| (*address, *count, *instruction))
+            .collect::<Vec<_>>();
+        hot.sort_by_key(|(address, count, _)| (std::cmp::Reverse(*count), *address));
+        hot.truncate(n);
+        hot
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.instructions.max(1) as f64
+    }
+}
+
+impl Observer for Profile {
+    fn observe(&mut self, record: &Record) {
+        let instruction = record.instruction;
+        self.instructions += 1;
+        let entry = self
+            .addresses
+            .entry(record.address)
+            .or_insert((0, instruction));
+        entry.0 += 1;
+        entry.1 = instruction;
+        *self.opcodes.entry(instruction.opcode).or_insert(0) += 1;
+    }
+}
+
+/// A report of the totals, the hottest addresses and the opcode histogram
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions at {} addresses",
+            self.instructions,
+            self.addresses.len()
+        )?;
+        writeln!(f, "\nHot addresses:")?;
+        for (address, count, instruction) in self.hot_addresses(HOT_ADDRESSES) {
+            writeln!(
+                f,
+                "{:>8} {:>10} {:>6.2}%  {}",
+                address,
+                count,
+                self.percent(count),
+                instruction
+            )?;
+        }
+        writeln!(f, "\nOpcodes:")?;
+        let widest = self.opcodes.values().max().copied().unwrap_or(1);
+        for (opcode, count) in &self.opcodes {
+            let bar = "#".repeat((40 * count / widest) as usize);
+            writeln!(
+                f,
+                "{:>8} {:>10} {:>6.2}%  {}",
+                opcode.mnemonic(),
+                count,
+                self.percent(*count),
+                bar
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Vm};
+
+    #[test]
+    fn counts_a_loop() {
+        let program = assemble(
+            "
+                    in [n]
+            loop:   add [n], -1, [n]
+                    jnz [n], loop
+                    hlt
+            n:      db 0
+            ",
+        )
+        .unwrap();
+        let mut profile = Profile::new();
+        Vm::new(program.clone())
+            .with_input(vec![5])
+            .run_observed(&mut profile)
+            .unwrap();
+        assert_eq!(profile.instructions, 1 + 5 * 2 + 1);
+        assert_eq!(profile.opcodes[&Opcode::Add], 5);
+        let hot = profile.hot_addresses(2);
+        assert_eq!((hot[0].0, hot[0].1), (2, 5));
+        assert_eq!((hot[1].0, hot[1].1), (6, 5));
+
+        let report = profile.to_string();
+        assert!(report.starts_with("12 instructions at 4 addresses\n"));
+        assert!(report.contains("       2          5  41.67%  add [10], -1, [10]\n"));
+
+        // The input that fails isn't counted as run
+        let mut profile = Profile::new();
+        assert!(Vm::new(program).run_observed(&mut profile).is_err());
+        assert_eq!(profile.instructions, 0);
+    }
+}
@@ -0,0 +1,67 @@
+//! Saving a VM's state to come back to later, or to branch off from.
+//!
+//! Memory is copy on write, so taking a snapshot only copies page pointers
+//! and the I/O queues, and each fork only pays for the pages it writes to.
+use crate::Vm;
+
+/// A VM as it was at some point, which can be restored or forked any number
+/// of times
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    vm: Vm,
+}
+
+impl Snapshot {
+    /// A new VM starting from the snapshot
+    pub fn fork(&self) -> Vm {
+        self.vm.clone()
+    }
+}
+
+impl Vm {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { vm: self.clone() }
+    }
+
+    /// Put the VM back the way it was when the snapshot was taken
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.clone_from(&snapshot.vm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn restores_and_forks() {
+        let program = assemble(
+            "
+                    in [n]
+            loop:   out [n]
+                    add [n], -1, [n]
+                    jnz [n], loop
+                    hlt
+            n:      db 0
+            ",
+        )
+        .unwrap();
+        let mut vm = Vm::new(program).with_input(vec![3]);
+        vm.step().unwrap();
+        let snapshot = vm.snapshot();
+
+        vm.run().unwrap();
+        assert_eq!(vm.output, vec![3, 2, 1]);
+        vm.restore(&snapshot);
+        assert_eq!(vm, snapshot.fork());
+        assert!(vm.output.is_empty());
+
+        let mut fork = snapshot.fork();
+        fork.memory.set(12, 5).unwrap();
+        fork.run().unwrap();
+        assert_eq!(fork.output, vec![5, 4, 3, 2, 1]);
+        vm.run().unwrap();
+        assert_eq!(vm.output, vec![3, 2, 1]);
+    }
+}
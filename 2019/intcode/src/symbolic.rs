@@ -0,0 +1,385 @@
+//! Running a program with some memory cells left as unknowns.
+//!
+//! Every cell holds a linear expression over the unknowns instead of a
+//! number, so a program like day 2's can be solved for a result rather than
+//! searched. Anything that needs an unknown to be known, like writing through
+//! it or branching on it, is an error. Reading through one is allowed, as long
+//! as nothing uses what was read.
+use crate::instruction::{Instruction, Mode, Opcode};
+use crate::{Memory, VmError};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Give up on programs that run longer than this
+const STEP_LIMIT: usize = 10_000_000;
+
+/// `constant + sum(coefficient * unknown)`, with unknowns named by the
+/// address they started at
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i64,
+    pub terms: BTreeMap<usize, i64>,
+}
+
+impl Linear {
+    pub fn constant(value: i64) -> Self {
+        Linear {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn unknown(address: usize) -> Self {
+        Linear {
+            constant: 0,
+            terms: vec![(address, 1)].into_iter().collect(),
+        }
+    }
+
+    /// The value, if it doesn't depend on any unknowns
+    pub fn as_constant(&self) -> Option<i64> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    /// How much the expression goes up for each one `address` goes up
+    pub fn coefficient(&self, address: usize) -> i64 {
+        self.terms.get(&address).copied().unwrap_or(0)
+    }
+
+    fn add(&self, other: &Linear) -> Option<Linear> {
+        let mut sum = self.clone();
+        sum.constant = sum.constant.checked_add(other.constant)?;
+        for (unknown, coefficient) in &other.terms {
+            let term = sum.terms.entry(*unknown).or_insert(0);
+            *term = term.checked_add(*coefficient)?;
+            if *term == 0 {
+                sum.terms.remove(unknown);
+            }
+        }
+        Some(sum)
+    }
+
+    fn scale(&self, factor: i64) -> Option<Linear> {
+        if factor == 0 {
+            return Some(Linear::constant(0));
+        }
+        Some(Linear {
+            constant: self.constant.checked_mul(factor)?,
+            terms: self
+                .terms
+                .iter()
+                .map(|(unknown, coefficient)| Some((*unknown, coefficient.checked_mul(factor)?)))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Plug in values for the unknowns, which default to 0
+    pub fn evaluate(&self, values: &BTreeMap<usize, i64>) -> Result<i64, SymbolicError> {
+        self.terms
+            .iter()
+            .try_fold(self.constant, |total, (unknown, coefficient)| {
+                coefficient
+                    .checked_mul(values.get(unknown).copied().unwrap_or(0))
+                    .and_then(|term| total.checked_add(term))
+            })
+            .ok_or(SymbolicError::Overflow)
+    }
+}
+
+/// Like `3 + 100*[1] + [2]`
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.constant)?;
+        for (unknown, coefficient) in &self.terms {
+            let sign = if *coefficient < 0 { '-' } else { '+' };
+            match coefficient.abs() {
+                1 => write!(f, " {} [{}]", sign, unknown)?,
+                c => write!(f, " {} {}*[{}]", sign, c, unknown)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    Vm(VmError),
+    /// The instruction at `address` multiplied two unknowns together
+    NonLinear {
+        address: usize,
+    },
+    /// The instruction at `address` needed to know an unknown, to use it as
+    /// an address, an opcode or a condition
+    DependsOnUnknown {
+        address: usize,
+    },
+    /// The instruction at `address` does I/O, which has no symbolic meaning
+    Unsupported {
+        address: usize,
+        opcode: Opcode,
+    },
+    /// The result at `address` came from memory read through an unknown
+    /// address
+    LostTrack {
+        address: usize,
+    },
+    TooLong,
+    /// Plugging values into an expression overflowed an i64
+    Overflow,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Vm(error) => write!(f, "{}", error),
+            SymbolicError::NonLinear { address } => write!(
+                f,
+                "Instruction at address {} multiplies two unknowns",
+                address
+            ),
+            SymbolicError::DependsOnUnknown { address } => write!(
+                f,
+                "Instruction at address {} depends on an unknown",
+                address
+            ),
+            SymbolicError::Unsupported { address, opcode } => write!(
+                f,
+                "Can't run {} at address {} symbolically",
+                opcode.mnemonic(),
+                address
+            ),
+            SymbolicError::LostTrack { address } => write!(
+                f,
+                "Address {} was computed from an unknown address",
+                address
+            ),
+            SymbolicError::TooLong => write!(f, "Ran for more than {} steps", STEP_LIMIT),
+            SymbolicError::Overflow => write!(f, "Evaluating the expression overflows an i64"),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+impl From<VmError> for SymbolicError {
+    fn from(error: VmError) -> Self {
+        SymbolicError::Vm(error)
+    }
+}
+
+/// Cells are `None` once they've been written with something read through an
+/// unknown address, which is fine as long as nothing uses them
+struct Machine {
+    memory: Vec<Option<Linear>>,
+    ip: usize,
+    relative_base: i64,
+}
+
+impl Machine {
+    fn get(&self, address: usize) -> Option<Linear> {
+        self.memory
+            .get(address)
+            .cloned()
+            .unwrap_or_else(|| Some(Linear::default()))
+    }
+
+    /// A value that has to be known for the current instruction to run
+    fn known(&self, value: Option<Linear>) -> Result<i64, SymbolicError> {
+        value
+            .as_ref()
+            .and_then(Linear::as_constant)
+            .ok_or(SymbolicError::DependsOnUnknown { address: self.ip })
+    }
+
+    /// The address parameter `n` of the current instruction refers to
+    fn address(&self, mode: Mode, n: usize) -> Result<usize, SymbolicError> {
+        let value = self.known(self.get(self.ip + 1 + n))?;
+        let address = match mode {
+            Mode::Relative => self.relative_base.checked_add(value),
+            _ => Some(value),
+        }
+        .ok_or(VmError::Overflow { address: self.ip })?;
+        Ok(Memory::address(address)?)
+    }
+
+    /// Reading through an unknown address gives `None`, rather than failing
+    fn read(&self, mode: Mode, n: usize) -> Result<Option<Linear>, SymbolicError> {
+        match mode {
+            Mode::Immediate => Ok(self.get(self.ip + 1 + n)),
+            _ => match self.address(mode, n) {
+                Ok(address) => Ok(self.get(address)),
+                Err(SymbolicError::DependsOnUnknown { .. }) => Ok(None),
+                Err(error) => Err(error),
+            },
+        }
+    }
+
+    fn write(&mut self, mode: Mode, n: usize, value: Option<Linear>) -> Result<(), SymbolicError> {
+        let address = self.address(mode, n)?;
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, Some(Linear::default()));
+        }
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    /// Execute one instruction, returning false once halted
+    fn step(&mut self) -> Result<bool, SymbolicError> {
+        let address = self.ip;
+        self.known(self.get(address))?;
+        // Only the opcode and modes come from decoding, since parameters may
+        // be unknown
+        let instruction = Instruction::decode(address, |a| {
+            self.get(a)
+                .and_then(|value| value.as_constant())
+                .unwrap_or(0)
+        })?;
+        let modes = instruction
+            .parameters()
+            .iter()
+            .map(|parameter| parameter.mode)
+            .collect::<Vec<Mode>>();
+        let mut next = address + instruction.size();
+        let overflow = SymbolicError::Vm(VmError::Overflow { address });
+
+        match instruction.opcode {
+            Opcode::Add | Opcode::Multiply => {
+                let result = match (self.read(modes[0], 0)?, self.read(modes[1], 1)?) {
+                    (Some(left), Some(right)) => Some(
+                        if instruction.opcode == Opcode::Add {
+                            left.add(&right)
+                        } else {
+                            match (left.as_constant(), right.as_constant()) {
+                                (Some(factor), _) => right.scale(factor),
+                                (_, Some(factor)) => left.scale(factor),
+                                _ => return Err(SymbolicError::NonLinear { address }),
+                            }
+                        }
+                        .ok_or(overflow)?,
+                    ),
+                    _ => None,
+                };
+                self.write(modes[2], 2, result)?;
+            }
+            Opcode::LessThan | Opcode::Equals => {
+                let left = self.known(self.read(modes[0], 0)?)?;
+                let right = self.known(self.read(modes[1], 1)?)?;
+                let result = if instruction.opcode == Opcode::LessThan {
+                    left < right
+                } else {
+                    left == right
+                };
+                self.write(modes[2], 2, Some(Linear::constant(result as i64)))?;
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = self.known(self.read(modes[0], 0)?)?;
+                if (condition != 0) == (instruction.opcode == Opcode::JumpIfTrue) {
+                    let target = self.known(self.read(modes[1], 1)?)?;
+                    next = Memory::address(target)?;
+                }
+            }
+            Opcode::AdjustRelativeBase => {
+                let offset = self.known(self.read(modes[0], 0)?)?;
+                self.relative_base = self.relative_base.checked_add(offset).ok_or(overflow)?;
+            }
+            Opcode::Input | Opcode::Output => {
+                return Err(SymbolicError::Unsupported {
+                    address,
+                    opcode: instruction.opcode,
+                })
+            }
+            Opcode::Halt => return Ok(false),
+        }
+        self.ip = next;
+        Ok(true)
+    }
+}
+
+/// Run `program` with the cells at `unknowns` left unknown, and return the
+/// expression left at `result` when it halts
+pub fn evaluate_symbolic(
+    program: &[i64],
+    unknowns: &[usize],
+    result: usize,
+) -> Result<Linear, SymbolicError> {
+    let mut machine = Machine {
+        memory: program
+            .iter()
+            .map(|value| Some(Linear::constant(*value)))
+            .collect(),
+        ip: 0,
+        relative_base: 0,
+    };
+    for unknown in unknowns {
+        if *unknown >= machine.memory.len() {
+            machine.memory.resize(unknown + 1, Some(Linear::default()));
+        }
+        machine.memory[*unknown] = Some(Linear::unknown(*unknown));
+    }
+    for _ in 0..STEP_LIMIT {
+        if !machine.step()? {
+            return machine
+                .get(result)
+                .ok_or(SymbolicError::LostTrack { address: result });
+        }
+    }
+    Err(SymbolicError::TooLong)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Vm};
+
+    #[test]
+    fn builds_linear_expressions() {
+        // 0 = [1] * 100 + [2] + 7. Like day 2, the first instruction reads
+        // through the unknowns, but its result is never used.
+        let program = assemble(
+            "
+                    add [0], [0], [0]
+                    mul [1], 100, [hundreds]
+                    add [hundreds], [2], [0]
+                    add [0], 7, [0]
+                    hlt
+            hundreds: db 0
+            ",
+        )
+        .unwrap();
+        let expression = evaluate_symbolic(&program, &[1, 2], 0).unwrap();
+        assert_eq!(expression.to_string(), "7 + 100*[1] + [2]");
+
+        let values = vec![(1, 12), (2, 2)].into_iter().collect();
+        let mut vm = Vm::new(program);
+        vm.memory.set(1, 12).unwrap();
+        vm.memory.set(2, 2).unwrap();
+        vm.run().unwrap();
+        assert_eq!(expression.evaluate(&values), Ok(vm.memory.get(0)));
+        let values = vec![(1, i64::MAX), (2, 2)].into_iter().collect();
+        assert_eq!(expression.evaluate(&values), Err(SymbolicError::Overflow));
+    }
+
+    #[test]
+    fn rejects_what_it_cant_follow() {
+        assert_eq!(
+            evaluate_symbolic(&[2, 1, 2, 0, 99], &[1, 2], 0),
+            Err(SymbolicError::LostTrack { address: 0 })
+        );
+        assert_eq!(
+            evaluate_symbolic(&[1101, 1, 1, 0, 99], &[3], 0),
+            Err(SymbolicError::DependsOnUnknown { address: 0 })
+        );
+        assert_eq!(
+            evaluate_symbolic(&[1105, 0, 4, 99, 99], &[1], 0),
+            Err(SymbolicError::DependsOnUnknown { address: 0 })
+        );
+        assert_eq!(
+            evaluate_symbolic(&[2, 9, 10, 0, 99, 0, 0, 0, 0, 0, 0], &[9, 10], 0),
+            Err(SymbolicError::NonLinear { address: 0 })
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Recording every instruction a VM executes.
+//!
+//! Traces are written either as JSON lines, one object per instruction, or
+//! as a compact binary of zigzag LEB128 varints: the address, the encoded
+//! opcode and modes, the relative base and then each parameter. The opcode
+//! says how many parameters follow, so records need no framing.
+use crate::instruction::Instruction;
+use crate::vm::Observer;
+use crate::VmError;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Jsonl,
+    Binary,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(Format::Jsonl),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!(
+                "Unknown trace format {}, expected jsonl or binary",
+                s
+            )),
+        }
+    }
+}
+
+/// Why a binary trace couldn't be read back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceError {
+    /// The trace ended part way through its `record`th record
+    Truncated { record: usize },
+    /// A record held an instruction that doesn't decode
+    Corrupt(VmError),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Truncated { record } => {
+                write!(f, "Trace ends part way through record {}", record)
+            }
+            TraceError::Corrupt(error) => write!(f, "Corrupt trace: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<VmError> for TraceError {
+    fn from(error: VmError) -> Self {
+        TraceError::Corrupt(error)
+    }
+}
+
+/// One executed instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub address: usize,
+    pub relative_base: i64,
+    pub instruction: Instruction,
+}
+
+/// Writes a record per instruction to `writer`
+#[derive(Debug)]
+pub struct Tracer<W: Write> {
+    writer: W,
+    format: Format,
+    step: u64,
+    /// The first write error, since observers can't fail
+    error: Option<io::Error>,
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(writer: W, format: Format) -> Self {
+        Tracer {
+            writer,
+            format,
+            step: 0,
+            error: None,
+        }
+    }
+
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        let instruction = &record.instruction;
+        match self.format {
+            Format::Jsonl => {
+                let parameters = instruction
+                    .parameters()
+                    .iter()
+                    .map(|parameter| parameter.value.to_string())
+                    .collect::<Vec<_>>();
+                format!(
+                    "{{\"step\":{},\"ip\":{},\"rb\":{},\"op\":\"{}\",\"code\":{},\"params\":[{}]}}\n",
+                    self.step,
+                    record.address,
+                    record.relative_base,
+                    instruction.opcode.mnemonic(),
+                    instruction.encode()[0],
+                    parameters.join(",")
+                )
+                .into_bytes()
+            }
+            Format::Binary => {
+                let mut bytes = Vec::new();
+                write_varint(&mut bytes, record.address as u64);
+                let encoded = instruction.encode();
+                write_varint(&mut bytes, zigzag(encoded[0]));
+                write_varint(&mut bytes, zigzag(record.relative_base));
+                for value in &encoded[1..] {
+                    write_varint(&mut bytes, zigzag(*value));
+                }
+                bytes
+            }
+        }
+    }
+
+    /// Flush and hand back the writer, or the first error writing to it
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Observer for Tracer<W> {
+    fn observe(&mut self, record: &Record) {
+        if self.error.is_some() {
+            return;
+        }
+        let bytes = self.encode(record);
+        if let Err(error) = self.writer.write_all(&bytes) {
+            self.error = Some(error);
+        }
+        self.step += 1;
+    }
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes.next()?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Read back a binary trace. Fails with `Corrupt` on a record that doesn't
+/// decode, or `Truncated` if it ends part way through one.
+pub fn read_binary(bytes: &[u8]) -> Result<Vec<Record>, TraceError> {
+    let truncated = |record| TraceError::Truncated { record };
+    let mut bytes = bytes.iter().copied().peekable();
+    let mut records = Vec::new();
+    while bytes.peek().is_some() {
+        let position = records.len();
+        let address = read_varint(&mut bytes).ok_or_else(|| truncated(position))? as usize;
+        let code = unzigzag(read_varint(&mut bytes).ok_or_else(|| truncated(position))?);
+        let relative_base = unzigzag(read_varint(&mut bytes).ok_or_else(|| truncated(position))?);
+        // Decode the opcode on its own to find out how many parameters follow
+        let arity = Instruction::decode(0, |a| if a == 0 { code } else { 0 })?
+            .parameters()
+            .len();
+        let mut encoded = vec![code];
+        for _ in 0..arity {
+            encoded.push(unzigzag(
+                read_varint(&mut bytes).ok_or_else(|| truncated(position))?,
+            ));
+        }
+        records.push(Record {
+            address,
+            relative_base,
+            instruction: Instruction::decode_slice(&encoded, 0)?,
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vm;
+
+    fn traced(format: Format) -> Vec<u8> {
+        let mut tracer = Tracer::new(Vec::new(), format);
+        let mut vm = Vm::new(vec![109, -3, 1201, 7, 5, 0, 99, -70000]);
+        vm.run_observed(&mut tracer).unwrap();
+        tracer.finish().unwrap()
+    }
+
+    #[test]
+    fn jsonl() {
+        let trace = String::from_utf8(traced(Format::Jsonl)).unwrap();
+        assert_eq!(
+            trace,
+            "{\"step\":0,\"ip\":0,\"rb\":0,\"op\":\"arb\",\"code\":109,\"params\":[-3]}\n\
+             {\"step\":1,\"ip\":2,\"rb\":-3,\"op\":\"add\",\"code\":1201,\"params\":[7,5,0]}\n\
+             {\"step\":2,\"ip\":6,\"rb\":-3,\"op\":\"hlt\",\"code\":99,\"params\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        let trace = traced(Format::Binary);
+        let records = read_binary(&trace).unwrap();
+        let addresses = records.iter().map(|r| r.address).collect::<Vec<_>>();
+        assert_eq!(addresses, vec![0, 2, 6]);
+        assert_eq!(records[1].relative_base, -3);
+        assert_eq!(records[1].instruction.encode(), vec![1201, 7, 5, 0]);
+        assert_eq!(
+            read_binary(&trace[..trace.len() - 2]),
+            Err(TraceError::Truncated { record: 2 })
+        );
+        // Address 0, opcode 98 and relative base 0
+        assert_eq!(
+            read_binary(&[0, 0xc4, 0x01, 0]),
+            Err(TraceError::Corrupt(VmError::InvalidOpcode {
+                address: 0,
+                value: 98
+            }))
+        );
+        for value in [0, 1, -1, 63, -64, i64::MAX, i64::MIN] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Running Intcode programs.
+use crate::instruction::{Instruction, Mode, Opcode, Parameter};
+use crate::trace::Record;
+use crate::{Memory, VmError};
+use std::collections::VecDeque;
+
+/// Something that wants to see every instruction a VM executes
+pub trait Observer {
+    /// Called once an instruction has run without error, with the address
+    /// and relative base it ran with
+    fn observe(&mut self, record: &Record);
+}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    fn observe(&mut self, record: &Record) {
+        (**self).observe(record);
+    }
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn observe(&mut self, record: &Record) {
+        self.0.observe(record);
+        self.1.observe(record);
+    }
+}
+
+/// Where `Vm::resume` stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Waiting on an input instruction with nothing to read
+    NeedsInput,
+    /// Just wrote a value
+    Output(i64),
+    Halted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vm {
+    pub memory: Memory,
+    /// Address of the next instruction
+    pub ip: usize,
+    pub relative_base: i64,
+    /// Values waiting to be read by input instructions, front first
+    pub input: VecDeque<i64>,
+    /// Everything written by output instructions so far
+    pub output: Vec<i64>,
+    halted: bool,
+}
+
+impl Vm {
+    pub fn new(program: Vec<i64>) -> Self {
+        Vm {
+            memory: Memory::new(program),
+            ip: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            halted: false,
+        }
+    }
+
+    /// Queue up values for input instructions
+    pub fn with_input<I>(mut self, input: I) -> Self
+    where
+        I: IntoIterator<Item = i64>,
+    {
+        self.input.extend(input);
+        self
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// The instruction at the instruction pointer
+    pub fn current(&self) -> Result<Instruction, VmError> {
+        Instruction::decode(self.ip, |address| self.memory.get(address))
+    }
+
+    /// Execute a single instruction, doing nothing once halted. An input
+    /// instruction with nothing to read fails with `MissingInput` and leaves
+    /// the VM as it was, so it can be stepped again once there's input.
+    pub fn step(&mut self) -> Result<(), VmError> {
+        if self.halted {
+            return Ok(());
+        }
+        let instruction = self.memory.instruction(self.ip)?;
+        self.execute(&instruction)
+    }
+
+    /// Like `step`, but decoding the instruction afresh rather than using
+    /// the cache, to benchmark and check the cache against
+    pub fn step_uncached(&mut self) -> Result<(), VmError> {
+        if self.halted {
+            return Ok(());
+        }
+        let instruction = self.current()?;
+        self.execute(&instruction)
+    }
+
+    /// Run `instruction` as if it were at the instruction pointer
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), VmError> {
+        let params = instruction.parameters();
+        let mut next = self.ip + instruction.size();
+        match instruction.opcode {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+                let left = self.read(&params[0])?;
+                let right = self.read(&params[1])?;
+                let result = match instruction.opcode {
+                    Opcode::Add => left.checked_add(right),
+                    Opcode::Multiply => left.checked_mul(right),
+                    Opcode::LessThan => Some((left < right) as i64),
+                    _ => Some((left == right) as i64),
+                }
+                .ok_or(VmError::Overflow { address: self.ip })?;
+                self.write(&params[2], result)?;
+            }
+            Opcode::Input => {
+                let value = self
+                    .input
+                    .front()
+                    .copied()
+                    .ok_or(VmError::MissingInput { address: self.ip })?;
+                self.write(&params[0], value)?;
+                self.input.pop_front();
+            }
+            Opcode::Output => {
+                let value = self.read(&params[0])?;
+                self.output.push(value);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = self.read(&params[0])? != 0;
+                if condition == (instruction.opcode == Opcode::JumpIfTrue) {
+                    next = Memory::address(self.read(&params[1])?)?;
+                }
+            }
+            Opcode::AdjustRelativeBase => {
+                let offset = self.read(&params[0])?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(VmError::Overflow { address: self.ip })?;
+            }
+            Opcode::Halt => {
+                self.halted = true;
+                next = self.ip;
+            }
+        }
+        self.ip = next;
+        Ok(())
+    }
+
+    /// Run until the program halts, writes a value, or wants input it
+    /// doesn't have. Values handed back as `State::Output` aren't kept in
+    /// `output`.
+    pub fn resume(&mut self) -> Result<State, VmError> {
+        while !self.halted {
+            let written = self.output.len();
+            match self.step() {
+                Err(VmError::MissingInput { .. }) => return Ok(State::NeedsInput),
+                Err(error) => return Err(error),
+                Ok(()) if self.output.len() > written => {
+                    return Ok(State::Output(self.output.pop().expect("Just written")));
+                }
+                Ok(()) => {}
+            }
+        }
+        Ok(State::Halted)
+    }
+
+    /// Step until the program halts
+    pub fn run(&mut self) -> Result<(), VmError> {
+        while !self.halted {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Step until the program halts, showing `observer` each instruction
+    /// that succeeds
+    pub fn run_observed<O: Observer>(&mut self, observer: &mut O) -> Result<(), VmError> {
+        while !self.halted {
+            let record = Record {
+                address: self.ip,
+                relative_base: self.relative_base,
+                instruction: self.memory.instruction(self.ip)?,
+            };
+            self.execute(&record.instruction)?;
+            observer.observe(&record);
+        }
+        Ok(())
+    }
+
+    /// The address a position or relative parameter points at
+    fn address(&self, parameter: &Parameter) -> Result<usize, VmError> {
+        let address = match parameter.mode {
+            Mode::Position | Mode::Immediate => Some(parameter.value),
+            Mode::Relative => self.relative_base.checked_add(parameter.value),
+        }
+        .ok_or(VmError::Overflow { address: self.ip })?;
+        Memory::address(address)
+    }
+
+    fn read(&self, parameter: &Parameter) -> Result<i64, VmError> {
+        match parameter.mode {
+            Mode::Immediate => Ok(parameter.value),
+            _ => Ok(self.memory.get(self.address(parameter)?)),
+        }
+    }
+
+    fn write(&mut self, parameter: &Parameter, value: i64) -> Result<(), VmError> {
+        let address = self.address(parameter)?;
+        self.memory.set(address, value)
+    }
+}
+
+/// Run a program to completion on the given input, returning its output
+pub fn run<I>(program: Vec<i64>, input: I) -> Result<Vec<i64>, VmError>
+where
+    I: IntoIterator<Item = i64>,
+{
+    let mut vm = Vm::new(program).with_input(input);
+    vm.run()?;
+    Ok(vm.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_program};
+
+    #[test]
+    fn comparisons_and_jumps() {
+        // Day 5: is the input 8? Position mode, immediate mode, then jumps
+        let equals_eight = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(run(equals_eight.clone(), vec![8]), Ok(vec![1]));
+        assert_eq!(run(equals_eight, vec![7]), Ok(vec![0]));
+        let less_than_eight = vec![3, 3, 1107, -1, 8, 3, 4, 3, 99];
+        assert_eq!(run(less_than_eight, vec![5]), Ok(vec![1]));
+        let is_nonzero = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(run(is_nonzero.clone(), vec![0]), Ok(vec![0]));
+        assert_eq!(run(is_nonzero, vec![3]), Ok(vec![1]));
+
+        let compare = parse_program(
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+             1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+             999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        )
+        .unwrap();
+        assert_eq!(run(compare.clone(), vec![7]), Ok(vec![999]));
+        assert_eq!(run(compare.clone(), vec![8]), Ok(vec![1000]));
+        assert_eq!(run(compare, vec![9]), Ok(vec![1001]));
+    }
+
+    #[test]
+    fn relative_base_and_growing_memory() {
+        // Day 9: a program that outputs a copy of itself
+        let quine =
+            parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+        assert_eq!(run(quine.clone(), vec![]), Ok(quine));
+        assert_eq!(
+            run(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0], vec![]),
+            Ok(vec![1219070632396864])
+        );
+        assert_eq!(
+            run(vec![104, 1125899906842624, 99], vec![]),
+            Ok(vec![1125899906842624])
+        );
+    }
+
+    #[test]
+    fn waits_for_input() {
+        let mut vm = Vm::new(vec![3, 5, 4, 5, 99, 0]);
+        assert_eq!(vm.step(), Err(VmError::MissingInput { address: 0 }));
+        assert_eq!(vm.ip, 0);
+        vm.input.push_back(42);
+        vm.run().unwrap();
+        assert!(vm.halted());
+        assert_eq!(vm.output, vec![42]);
+    }
+
+    #[test]
+    fn pauses_on_input_and_output() {
+        let mut vm = Vm::new(vec![3, 9, 4, 9, 4, 9, 99, 0, 0, 0]);
+        assert_eq!(vm.resume(), Ok(State::NeedsInput));
+        vm.input.push_back(5);
+        assert_eq!(vm.resume(), Ok(State::Output(5)));
+        assert_eq!(vm.resume(), Ok(State::Output(5)));
+        assert_eq!(vm.resume(), Ok(State::Halted));
+        assert_eq!(vm.resume(), Ok(State::Halted));
+        assert!(vm.output.is_empty());
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        // Bumps the value its own output instruction writes
+        let program = assemble(
+            "
+            loop:   out 1
+                    add [loop + 1], 1, [loop + 1]
+                    add [n], -1, [n]
+                    jnz [n], loop
+                    hlt
+            n:      db 3
+            ",
+        )
+        .unwrap();
+        assert_eq!(run(program.clone(), vec![]), Ok(vec![1, 2, 3]));
+
+        let mut vm = Vm::new(program);
+        while !vm.halted() {
+            vm.step_uncached().unwrap();
+        }
+        assert_eq!(vm.output, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn reports_bad_addresses() {
+        assert_eq!(
+            run(vec![4, -1, 99], vec![]),
+            Err(VmError::InvalidAddress { address: -1 })
+        );
+        assert_eq!(
+            run(vec![1106, 0, -5], vec![]),
+            Err(VmError::InvalidAddress { address: -5 })
+        );
+    }
+}
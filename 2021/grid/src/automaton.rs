@@ -0,0 +1,194 @@
+//! Stepping cellular automata on a grid.
+//!
+//! Life-style puzzles compute every cell from the previous generation with
+//! `Automaton::step`. Octopus-style puzzles, where a cell going off can set
+//! off its neighbors, use `Automaton::cascade` inside a step. Either way
+//! `Automaton::run` drives the steps and lets the caller decide when to stop.
+use crate::neighborhood::Neighbors;
+use crate::{Grid, Index, Neighborhood};
+
+/// What `Automaton::run` should do after a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Automaton<'a, T> {
+    pub grid: Grid<T>,
+    pub neighborhood: Neighborhood<'a>,
+}
+
+/// The values of a cell's neighbors, as seen by a rule
+pub struct NeighborCells<'g, 'a, T> {
+    grid: &'g Grid<T>,
+    neighbors: Neighbors<'a>,
+}
+
+impl<'g, T> Iterator for NeighborCells<'g, '_, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<&'g T> {
+        let index = self.neighbors.next()?;
+        self.grid.get(&index)
+    }
+}
+
+impl<'a, T> Automaton<'a, T> {
+    pub fn new(grid: Grid<T>, neighborhood: Neighborhood<'a>) -> Self {
+        Automaton { grid, neighborhood }
+    }
+
+    /// Replace every cell at once with `rule(cell, neighbors)`, where the
+    /// neighbors are from before the step
+    pub fn step<R>(&mut self, rule: R)
+    where
+        R: Fn(&T, NeighborCells<'_, 'a, T>) -> T,
+    {
+        let grid = &self.grid;
+        let storage = grid
+            .indices()
+            .map(|index| {
+                let neighbors = NeighborCells {
+                    grid,
+                    neighbors: grid.neighbors_in(&index, self.neighborhood),
+                };
+                rule(
+                    grid.get(&index).expect("Index came from the grid"),
+                    neighbors,
+                )
+            })
+            .collect();
+        self.grid.storage = storage;
+    }
+
+    /// Every cell matching `fires` goes off once, calling `spread` on each of
+    /// its neighbors, which may make them go off in turn. Runs until nothing
+    /// new goes off and returns the cells that did, in order.
+    pub fn cascade<F, S>(&mut self, fires: F, spread: S) -> Vec<Index>
+    where
+        F: Fn(&T) -> bool,
+        S: Fn(&mut T),
+    {
+        let mut fired = Grid::filled(self.grid.length, self.grid.height, false);
+        let mut order = Vec::new();
+        let mut pending = self
+            .grid
+            .indices()
+            .filter(|index| fires(self.grid.get(index).expect("Index came from the grid")))
+            .collect::<Vec<Index>>();
+
+        while let Some(index) = pending.pop() {
+            let already = fired.get_mut(&index).expect("Index came from the grid");
+            if *already {
+                continue;
+            }
+            *already = true;
+            order.push(index);
+
+            for neighbor in self.grid.neighbors_in(&index, self.neighborhood) {
+                if *fired.get(&neighbor).expect("Neighbor is in the grid") {
+                    continue;
+                }
+                let cell = self
+                    .grid
+                    .get_mut(&neighbor)
+                    .expect("Neighbor is in the grid");
+                spread(cell);
+                if fires(cell) {
+                    pending.push(neighbor);
+                }
+            }
+        }
+        order
+    }
+
+    /// Call `step` up to `limit` times, handing each step's result to
+    /// `on_step` along with the step number (starting at 1). Returns the
+    /// step number `on_step` stopped at, or `None` if it never did.
+    pub fn run<S, R, C>(&mut self, limit: usize, mut step: S, mut on_step: C) -> Option<usize>
+    where
+        S: FnMut(&mut Self) -> R,
+        C: FnMut(&Self, usize, R) -> Control,
+    {
+        for generation in 1..=limit {
+            let result = step(self);
+            if on_step(self, generation, result) == Control::Stop {
+                return Some(generation);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn life(alive: &bool, neighbors: NeighborCells<bool>) -> bool {
+        let count = neighbors.filter(|neighbor| **neighbor).count();
+        matches!((alive, count), (true, 2) | (_, 3))
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let horizontal: Grid<bool> = ".....\n.....\n.###.\n.....\n.....".parse().unwrap();
+        let vertical: Grid<bool> = ".....\n..#..\n..#..\n..#..\n.....".parse().unwrap();
+        let mut automaton = Automaton::new(horizontal.clone(), Neighborhood::Moore);
+        automaton.step(life);
+        assert_eq!(automaton.grid, vertical);
+        automaton.step(life);
+        assert_eq!(automaton.grid, horizontal);
+    }
+
+    fn octopus_step(octopuses: &mut Automaton<usize>) -> usize {
+        octopuses
+            .grid
+            .storage
+            .iter_mut()
+            .for_each(|energy| *energy += 1);
+        let flashed = octopuses.cascade(|energy| *energy > 9, |energy| *energy += 1);
+        for index in &flashed {
+            *octopuses.grid.get_mut(index).expect("Flashed on the grid") = 0;
+        }
+        flashed.len()
+    }
+
+    #[test]
+    fn octopuses_cascade() {
+        let grid: Grid<usize> = "11111\n19991\n19191\n19991\n11111".parse().unwrap();
+        let mut automaton = Automaton::new(grid, Neighborhood::Moore);
+        let mut flashes = Vec::new();
+        let stopped = automaton.run(2, octopus_step, |_, _, flashed| {
+            flashes.push(flashed);
+            Control::Continue
+        });
+        assert_eq!(stopped, None);
+        assert_eq!(flashes, vec![9, 0]);
+        assert_eq!(
+            automaton.grid,
+            "45654\n51115\n61116\n51115\n45654".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn run_stops_when_asked() {
+        let grid: Grid<usize> = "0".parse().unwrap();
+        let mut automaton = Automaton::new(grid, Neighborhood::VonNeumann);
+        let stopped = automaton.run(
+            100,
+            |a| {
+                a.grid.storage[0] += 1;
+            },
+            |a, _, _| {
+                if a.grid.storage[0] == 7 {
+                    Control::Stop
+                } else {
+                    Control::Continue
+                }
+            },
+        );
+        assert_eq!(stopped, Some(7));
+    }
+}
@@ -0,0 +1,146 @@
+//! Flood fill and connected component labelling.
+use crate::{BoundingBox, Grid, Index, Neighborhood};
+
+/// A connected region of cells
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Cells in the order they were reached from the first one
+    pub cells: Vec<Index>,
+    pub bounds: BoundingBox,
+}
+
+impl Component {
+    pub fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+/// Every region of a grid, along with which region each cell belongs to
+#[derive(Debug, Clone)]
+pub struct Labelling {
+    /// The position of a cell's component in `components`, or `None` if the
+    /// cell didn't match the predicate
+    pub labels: Grid<Option<usize>>,
+    pub components: Vec<Component>,
+}
+
+impl<T> Grid<T> {
+    /// All cells connected to `start` through cells matching `predicate`.
+    /// Returns `None` if `start` is off the grid or doesn't match itself.
+    pub fn flood_fill<P>(
+        &self,
+        start: &Index,
+        neighborhood: Neighborhood,
+        predicate: P,
+    ) -> Option<Component>
+    where
+        P: Fn(&T) -> bool,
+    {
+        let mut seen = Grid::filled(self.length, self.height, false);
+        self.fill_from(start, neighborhood, &predicate, &mut seen)
+    }
+
+    /// Labels every connected region of cells matching `predicate`
+    pub fn components<P>(&self, neighborhood: Neighborhood, predicate: P) -> Labelling
+    where
+        P: Fn(&T) -> bool,
+    {
+        let mut seen = Grid::filled(self.length, self.height, false);
+        let mut labels = Grid::filled(self.length, self.height, None);
+        let mut components = Vec::new();
+
+        for index in self.indices() {
+            if let Some(component) = self.fill_from(&index, neighborhood, &predicate, &mut seen) {
+                for cell in &component.cells {
+                    *labels.get_mut(cell).expect("Cell came from the grid") =
+                        Some(components.len());
+                }
+                components.push(component);
+            }
+        }
+        Labelling { labels, components }
+    }
+
+    fn fill_from<P>(
+        &self,
+        start: &Index,
+        neighborhood: Neighborhood,
+        predicate: &P,
+        seen: &mut Grid<bool>,
+    ) -> Option<Component>
+    where
+        P: Fn(&T) -> bool,
+    {
+        if !self.get(start).is_some_and(predicate) || seen.get(start) != Some(&false) {
+            return None;
+        }
+
+        let mut cells = Vec::new();
+        let mut bounds = BoundingBox::from_index(start);
+        let mut stack = vec![*start];
+        *seen.get_mut(start).expect("Checked above") = true;
+
+        while let Some(current) = stack.pop() {
+            bounds.include(&current);
+            cells.push(current);
+            for neighbor in self.neighbors_in(&current, neighborhood) {
+                let visited = seen.get_mut(&neighbor).expect("Neighbor is in the grid");
+                if !*visited && predicate(self.get(&neighbor).expect("Neighbor is in the grid")) {
+                    *visited = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        Some(Component { cells, bounds })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_basins() {
+        let grid: Grid<usize> = "2199943210\n3987894921\n9856789892\n8767896789\n9899965678"
+            .parse()
+            .unwrap();
+        let labelling = grid.components(Neighborhood::VonNeumann, |height| *height != 9);
+
+        let mut sizes = labelling
+            .components
+            .iter()
+            .map(|c| c.size())
+            .collect::<Vec<usize>>();
+        sizes.sort_unstable();
+        assert_eq!(sizes, vec![3, 9, 9, 14]);
+
+        let top_left = labelling.labels.access(0, 0).unwrap().unwrap();
+        assert_eq!(labelling.labels.access(1, 0), Some(&Some(top_left)));
+        assert_eq!(labelling.labels.access(2, 0), Some(&None));
+        assert_eq!(
+            labelling.components[top_left].bounds,
+            BoundingBox {
+                min: Index { x: 0, y: 0 },
+                max: Index { x: 1, y: 1 },
+            }
+        );
+    }
+
+    #[test]
+    fn flood_fill_respects_neighborhood() {
+        let grid: Grid<bool> = "#.\n.#".parse().unwrap();
+        let origin = Index { x: 0, y: 0 };
+        let orthogonal = grid
+            .flood_fill(&origin, Neighborhood::VonNeumann, |filled| *filled)
+            .unwrap();
+        let diagonal = grid
+            .flood_fill(&origin, Neighborhood::Moore, |filled| *filled)
+            .unwrap();
+        assert_eq!(orthogonal.size(), 1);
+        assert_eq!(diagonal.size(), 2);
+        assert_eq!(
+            grid.flood_fill(&Index { x: 1, y: 0 }, Neighborhood::Moore, |filled| *filled),
+            None
+        );
+    }
+}
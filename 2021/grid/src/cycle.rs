@@ -0,0 +1,228 @@
+//! Finding where a simulation starts repeating itself, so we can skip ahead
+//! to far off steps without simulating every one of them.
+//!
+//! Works for any state that can be hashed and compared, grids included.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// States from step `start` onwards repeat every `period` steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// The earliest step with the same state as step `n`
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.period
+        }
+    }
+}
+
+/// Remembers every state it has been shown until one repeats
+#[derive(Debug, Clone)]
+pub struct CycleDetector<S> {
+    history: Vec<S>,
+    /// Steps in `history` by the hash of their state
+    seen: HashMap<u64, Vec<usize>>,
+    cycle: Option<Cycle>,
+}
+
+impl<S> Default for CycleDetector<S> {
+    fn default() -> Self {
+        CycleDetector {
+            history: Vec::new(),
+            seen: HashMap::new(),
+            cycle: None,
+        }
+    }
+}
+
+fn hash_of<S: Hash>(state: &S) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<S: Clone + Eq + Hash> CycleDetector<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state of the next step, the first call being step 0.
+    /// Returns the cycle as soon as a state repeats.
+    pub fn observe(&mut self, state: &S) -> Option<Cycle> {
+        if self.cycle.is_some() {
+            return self.cycle;
+        }
+        let step = self.history.len();
+        let history = &self.history;
+        let steps = self.seen.entry(hash_of(state)).or_default();
+        if let Some(start) = steps.iter().find(|seen| history[**seen] == *state) {
+            self.cycle = Some(Cycle {
+                start: *start,
+                period: step - start,
+            });
+            return self.cycle;
+        }
+        steps.push(step);
+        self.history.push(state.clone());
+        None
+    }
+
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    /// Every distinct state seen, in step order
+    pub fn history(&self) -> &[S] {
+        &self.history
+    }
+
+    /// The state at step `n`, if it has been seen or can be worked out from
+    /// the cycle
+    pub fn state_at(&self, n: usize) -> Option<&S> {
+        let step = match self.cycle {
+            Some(cycle) => cycle.equivalent_step(n),
+            None => n,
+        };
+        self.history.get(step)
+    }
+}
+
+/// Run `step` from `initial` until a state repeats
+pub fn find_cycle<S, F>(initial: S, step: F) -> (Cycle, CycleDetector<S>)
+where
+    S: Clone + Eq + Hash,
+    F: Fn(&S) -> S,
+{
+    let mut detector = CycleDetector::new();
+    let mut state = initial;
+    loop {
+        if let Some(cycle) = detector.observe(&state) {
+            return (cycle, detector);
+        }
+        state = step(&state);
+    }
+}
+
+/// The state after `n` steps, only simulating until a state repeats
+pub fn state_at<S, F>(initial: S, step: F, n: usize) -> S
+where
+    S: Clone + Eq + Hash,
+    F: Fn(&S) -> S,
+{
+    let mut detector = CycleDetector::new();
+    let mut state = initial;
+    for _ in 0..n {
+        if detector.observe(&state).is_some() {
+            break;
+        }
+        state = step(&state);
+    }
+    match detector.cycle() {
+        Some(_) => detector
+            .state_at(n)
+            .expect("The cycle covers every step")
+            .clone(),
+        None => state,
+    }
+}
+
+/// Brent's algorithm, which only keeps two states around at a time. Slower
+/// than `find_cycle` but works when the history won't fit in memory.
+pub fn brent<S, F>(initial: S, step: F) -> Cycle
+where
+    S: Clone + PartialEq,
+    F: Fn(&S) -> S,
+{
+    // Find the period by racing ahead in powers of two
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    // Then find the start by walking two states `period` apart
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+    Cycle { start, period }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Automaton, Grid, Neighborhood};
+
+    /// 0, 1, 2, 3, 4, 5, 3, 4, 5, ...
+    fn rho(n: &usize) -> usize {
+        if *n == 5 {
+            3
+        } else {
+            n + 1
+        }
+    }
+
+    #[test]
+    fn finds_start_and_period() {
+        let expected = Cycle {
+            start: 3,
+            period: 3,
+        };
+        let (cycle, detector) = find_cycle(0, rho);
+        assert_eq!(cycle, expected);
+        assert_eq!(detector.history(), &[0, 1, 2, 3, 4, 5]);
+        assert_eq!(brent(0, rho), expected);
+    }
+
+    #[test]
+    fn jumps_ahead() {
+        assert_eq!(state_at(0, rho, 2), 2);
+        assert_eq!(state_at(0, rho, 1_000_000_000), 4);
+        assert_eq!(
+            Cycle {
+                start: 3,
+                period: 3
+            }
+            .equivalent_step(9),
+            3
+        );
+    }
+
+    #[test]
+    fn grid_states() {
+        let glider: Grid<bool> = ".#..\n..#.\n###.\n....".parse().unwrap();
+        let step = |grid: &Grid<bool>| {
+            let mut life = Automaton::new(grid.clone(), Neighborhood::Moore);
+            life.step(|alive, neighbors| {
+                let count = neighbors.filter(|neighbor| **neighbor).count();
+                matches!((alive, count), (true, 2) | (_, 3))
+            });
+            life.grid
+        };
+        let (cycle, _) = find_cycle(glider.clone(), step);
+        assert_eq!(brent(glider, step), cycle);
+    }
+}
@@ -1,19 +1,40 @@
-use anyhow::Result;
+use std::fmt;
 use std::io;
 use std::io::BufRead;
+use std::str::FromStr;
+
+pub mod automaton;
+pub mod components;
+pub mod cycle;
+pub mod neighborhood;
+pub mod render;
+pub mod search;
+pub mod sparse;
+pub mod transform;
+pub mod view;
+
+pub use automaton::{Automaton, Control};
+pub use components::{Component, Labelling};
+pub use cycle::{Cycle, CycleDetector};
+pub use neighborhood::Neighborhood;
+pub use render::{Image, Overlay, Rgb};
+pub use sparse::SparseGrid;
+pub use transform::Fold;
+pub use view::{GridView, Tiled};
 
 /// A grid used to store values and access elements via neighbors
-pub struct Grid {
-    pub storage: Vec<usize>,
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Grid<T> {
+    pub storage: Vec<T>,
     pub height: usize,
     pub length: usize,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
-pub struct Point {
+pub struct Point<T> {
     pub x: isize,
     pub y: isize,
-    pub value: usize,
+    pub value: T,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Ord, PartialOrd)]
@@ -22,22 +43,211 @@ pub struct Index {
     pub y: isize,
 }
 
-impl Grid {
-    pub fn from_stdin(length: usize, height: usize) -> Result<Grid> {
+impl<T> Point<T> {
+    pub fn index(&self) -> Index {
+        Index {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+/// The smallest rectangle, inclusive on both ends, covering a set of indices
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Index,
+    pub max: Index,
+}
+
+impl BoundingBox {
+    pub fn from_index(index: &Index) -> Self {
+        BoundingBox {
+            min: *index,
+            max: *index,
+        }
+    }
+
+    /// Grow the box to cover `index`
+    pub fn include(&mut self, index: &Index) {
+        self.min.x = self.min.x.min(index.x);
+        self.min.y = self.min.y.min(index.y);
+        self.max.x = self.max.x.max(index.x);
+        self.max.y = self.max.y.max(index.y);
+    }
+
+    pub fn contains(&self, index: &Index) -> bool {
+        (self.min.x..=self.max.x).contains(&index.x) && (self.min.y..=self.max.y).contains(&index.y)
+    }
+
+    pub fn length(&self) -> usize {
+        (self.max.x - self.min.x) as usize + 1
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max.y - self.min.y) as usize + 1
+    }
+}
+
+/// Reasons a grid can fail to build from its input
+#[derive(Debug)]
+pub enum GridError {
+    /// A row didn't match the width of the first row
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The parse hook didn't accept a char
+    InvalidChar {
+        x: usize,
+        y: usize,
+        found: char,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(f, "Row {} has {} cells, expected {}", row, found, expected),
+            GridError::InvalidChar { x, y, found } => {
+                write!(f, "Invalid char {:?} at ({}, {})", found, x, y)
+            }
+            GridError::Io(e) => write!(f, "Failed to read grid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GridError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GridError {
+    fn from(e: io::Error) -> Self {
+        GridError::Io(e)
+    }
+}
+
+/// Parse hook used when building a grid out of text, one char per cell
+pub trait FromChar: Sized {
+    fn from_char(c: char) -> Option<Self>;
+}
+
+impl FromChar for usize {
+    fn from_char(c: char) -> Option<Self> {
+        c.to_digit(10).map(|digit| digit as usize)
+    }
+}
+
+impl FromChar for char {
+    fn from_char(c: char) -> Option<Self> {
+        Some(c)
+    }
+}
+
+/// `#` is filled, `.` is empty, like most of the puzzle maps
+impl FromChar for bool {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '#' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromChar> Grid<T> {
+    /// Read a grid from stdin, working out the dimensions from the lines
+    pub fn from_stdin() -> Result<Grid<T>, GridError> {
         let stdin = io::stdin();
         let handle = stdin.lock();
-        let lines = handle.lines();
-
-        let mut storage = Vec::new();
-
-        for line in lines {
-            let line = line?;
-            storage.append(
-                &mut line
-                    .chars()
-                    .map(|c| c.to_digit(10).unwrap() as usize)
-                    .collect::<Vec<usize>>(),
-            )
+        Grid::from_reader(handle)
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Grid<T>, GridError> {
+        Grid::from_reader_with(reader, T::from_char)
+    }
+
+    pub fn from_vec_str(lines: Vec<String>) -> Result<Grid<T>, GridError> {
+        Grid::from_lines(lines)
+    }
+
+    pub fn from_lines<I, S>(lines: I) -> Result<Grid<T>, GridError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Grid::from_lines_with(lines, T::from_char)
+    }
+}
+
+impl<T: FromChar> FromStr for Grid<T> {
+    type Err = GridError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Grid::from_lines(s.lines())
+    }
+}
+
+impl<T> Grid<T> {
+    /// Like `from_reader`, but each char is turned into a cell by `parse`
+    pub fn from_reader_with<R, F>(reader: R, parse: F) -> Result<Grid<T>, GridError>
+    where
+        R: BufRead,
+        F: Fn(char) -> Option<T>,
+    {
+        let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+        Grid::from_lines_with(lines, parse)
+    }
+
+    /// Like `from_lines`, but each char is turned into a cell by `parse`.
+    /// The width comes from the first line and the height from the number
+    /// of lines; trailing blank lines are ignored.
+    pub fn from_lines_with<I, S, F>(lines: I, parse: F) -> Result<Grid<T>, GridError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        F: Fn(char) -> Option<T>,
+    {
+        let mut rows = Vec::new();
+        for (y, line) in lines.into_iter().enumerate() {
+            let line = line.as_ref().trim_end_matches('\r');
+            let row = line
+                .chars()
+                .enumerate()
+                .map(|(x, c)| parse(c).ok_or(GridError::InvalidChar { x, y, found: c }))
+                .collect::<Result<Vec<T>, GridError>>()?;
+            rows.push(row);
+        }
+        while rows.last().map(|row| row.is_empty()).unwrap_or(false) {
+            rows.pop();
+        }
+        Grid::from_rows(rows)
+    }
+
+    /// Build a grid out of already parsed rows, e.g. whitespace separated numbers
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Grid<T>, GridError> {
+        let height = rows.len();
+        let length = rows.first().map(|row| row.len()).unwrap_or(0);
+        let mut storage = Vec::with_capacity(length * height);
+        for (y, mut row) in rows.into_iter().enumerate() {
+            if row.len() != length {
+                return Err(GridError::RaggedRow {
+                    row: y,
+                    expected: length,
+                    found: row.len(),
+                });
+            }
+            storage.append(&mut row);
         }
         Ok(Grid {
             length,
@@ -46,24 +256,25 @@ impl Grid {
         })
     }
 
-    pub fn from_vec_str(length: usize, height: usize, lines: Vec<String>) -> Result<Grid> {
-        let mut storage: Vec<usize> = Vec::new();
-        for line in lines {
-            storage.append(
-                &mut line
-                    .chars()
-                    .map(|c| c.to_digit(10).unwrap() as usize)
-                    .collect::<Vec<usize>>(),
-            )
+    /// A grid where each cell is `f` of its index
+    pub fn from_fn<F>(length: usize, height: usize, f: F) -> Grid<T>
+    where
+        F: Fn(Index) -> T,
+    {
+        let mut storage = Vec::with_capacity(length * height);
+        for y in 0..height as isize {
+            for x in 0..length as isize {
+                storage.push(f(Index { x, y }));
+            }
         }
-        Ok(Grid {
+        Grid {
+            storage,
             length,
             height,
-            storage,
-        })
+        }
     }
 
-    pub fn access_mut(&mut self, x: isize, y: isize) -> Option<&mut usize> {
+    pub fn access_mut(&mut self, x: isize, y: isize) -> Option<&mut T> {
         match self.storage_index(x, y) {
             Some(value) => Some(&mut self.storage[value]),
             None => None,
@@ -72,12 +283,8 @@ impl Grid {
 
     /// Given a position, access the value directly
     /// DEPRECATED (lol its funny to write this in a personal project)
-    pub fn access(&self, x: isize, y: isize) -> Option<usize> {
-        self.storage_index(x, y).map(|value| self.storage[value])
-    }
-    /// Access, but using the point as a public structure
-    pub fn get_point(&self, x: isize, y: isize) -> Option<Point> {
-        self.access(x, y).map(|value| Point { x, y, value })
+    pub fn access(&self, x: isize, y: isize) -> Option<&T> {
+        self.storage_index(x, y).map(|value| &self.storage[value])
     }
 
     fn storage_index(&self, x: isize, y: isize) -> Option<usize> {
@@ -88,28 +295,88 @@ impl Grid {
         }
     }
 
-    pub fn get(&self, index: &Index) -> Option<usize> {
+    /// Every index in the grid, row by row
+    pub fn indices(&self) -> impl Iterator<Item = Index> {
+        let length = self.length as isize;
+        (0..self.height as isize).flat_map(move |y| (0..length).map(move |x| Index { x, y }))
+    }
+
+    pub fn get(&self, index: &Index) -> Option<&T> {
         self.access(index.x, index.y)
     }
 
+    pub fn get_mut(&mut self, index: &Index) -> Option<&mut T> {
+        self.access_mut(index.x, index.y)
+    }
+
+    /// The four orthogonal neighbors inside the grid, see `neighbors_in` for
+    /// other neighborhoods
     pub fn neighbors(&self, index: &Index) -> Vec<Index> {
-        let mut to_return = Vec::new();
-        for x in [-1, 1] {
-            if self.storage_index(index.x + x, index.y).is_some() {
-                to_return.push(Index {
-                    x: index.x + x,
-                    y: index.y,
-                });
-            }
-        }
-        for y in [-1, 1] {
-            if self.storage_index(index.x, index.y + y).is_some() {
-                to_return.push(Index {
-                    x: index.x,
-                    y: index.y + y,
-                });
-            }
+        self.neighbors_in(index, Neighborhood::VonNeumann).collect()
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// A grid with every cell set to `value`
+    pub fn filled(length: usize, height: usize, value: T) -> Grid<T> {
+        Grid {
+            storage: vec![value; length * height],
+            length,
+            height,
         }
-        to_return
+    }
+
+    /// Access, but using the point as a public structure
+    pub fn get_point(&self, x: isize, y: isize) -> Option<Point<T>> {
+        self.access(x, y)
+            .cloned()
+            .map(|value| Point { x, y, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_dimensions() -> Result<(), GridError> {
+        let grid: Grid<usize> = "123\n456\n".parse()?;
+        assert_eq!(grid.length, 3);
+        assert_eq!(grid.height, 2);
+        assert_eq!(grid.access(2, 1), Some(&6));
+        Ok(())
+    }
+
+    #[test]
+    fn reads_any_bufread() -> Result<(), GridError> {
+        let grid: Grid<bool> = Grid::from_reader("#.\r\n.#\r\n".as_bytes())?;
+        assert_eq!(grid.storage, vec![true, false, false, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        let result = "123\n45\n".parse::<Grid<usize>>();
+        assert!(matches!(
+            result,
+            Err(GridError::RaggedRow {
+                row: 1,
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_non_digits() {
+        let result = "12\n3x\n".parse::<Grid<usize>>();
+        assert!(matches!(
+            result,
+            Err(GridError::InvalidChar {
+                x: 1,
+                y: 1,
+                found: 'x'
+            })
+        ));
     }
 }
@@ -0,0 +1,150 @@
+//! Which cells count as neighbors of a cell.
+use crate::{Grid, Index};
+
+const VON_NEUMANN: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+const MOORE: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const KNIGHT: [(isize, isize); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+/// A set of `(dx, dy)` offsets describing the neighbors of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood<'a> {
+    /// The four orthogonal cells
+    VonNeumann,
+    /// The eight orthogonal and diagonal cells
+    Moore,
+    /// The eight cells a chess knight can jump to
+    Knight,
+    /// Any other set of offsets
+    Custom(&'a [(isize, isize)]),
+}
+
+impl<'a> Neighborhood<'a> {
+    pub fn offsets(self) -> &'a [(isize, isize)] {
+        match self {
+            Neighborhood::VonNeumann => &VON_NEUMANN,
+            Neighborhood::Moore => &MOORE,
+            Neighborhood::Knight => &KNIGHT,
+            Neighborhood::Custom(offsets) => offsets,
+        }
+    }
+
+    /// Every index around `index` in this neighborhood, whether or not it is
+    /// on a grid
+    pub fn around(self, index: &Index) -> impl Iterator<Item = Index> + 'a {
+        let index = *index;
+        self.offsets().iter().map(move |(dx, dy)| Index {
+            x: index.x + dx,
+            y: index.y + dy,
+        })
+    }
+}
+
+/// The neighbors of an index that fall inside a `length` by `height` grid
+#[derive(Debug, Clone)]
+pub struct Neighbors<'a> {
+    center: Index,
+    offsets: std::slice::Iter<'a, (isize, isize)>,
+    length: isize,
+    height: isize,
+}
+
+impl<'a> Neighbors<'a> {
+    pub fn new(
+        center: &Index,
+        neighborhood: Neighborhood<'a>,
+        length: usize,
+        height: usize,
+    ) -> Self {
+        Neighbors {
+            center: *center,
+            offsets: neighborhood.offsets().iter(),
+            length: length as isize,
+            height: height as isize,
+        }
+    }
+}
+
+impl Iterator for Neighbors<'_> {
+    type Item = Index;
+
+    fn next(&mut self) -> Option<Index> {
+        for (dx, dy) in self.offsets.by_ref() {
+            let neighbor = Index {
+                x: self.center.x + dx,
+                y: self.center.y + dy,
+            };
+            if (0..self.length).contains(&neighbor.x) && (0..self.height).contains(&neighbor.y) {
+                return Some(neighbor);
+            }
+        }
+        None
+    }
+}
+
+impl<T> Grid<T> {
+    /// Whether the index is inside the grid
+    pub fn contains(&self, index: &Index) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Neighbors of `index` that are inside the grid, without allocating
+    pub fn neighbors_in<'a>(&self, index: &Index, neighborhood: Neighborhood<'a>) -> Neighbors<'a> {
+        Neighbors::new(index, neighborhood, self.length, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clips_to_the_grid() {
+        let grid: Grid<usize> = "123\n456\n789".parse().unwrap();
+        let corner = Index { x: 0, y: 0 };
+        let center = Index { x: 1, y: 1 };
+
+        assert_eq!(
+            grid.neighbors_in(&corner, Neighborhood::VonNeumann).count(),
+            2
+        );
+        assert_eq!(grid.neighbors_in(&corner, Neighborhood::Moore).count(), 3);
+        assert_eq!(grid.neighbors_in(&center, Neighborhood::Moore).count(), 8);
+        assert_eq!(grid.neighbors_in(&center, Neighborhood::Knight).count(), 0);
+        assert_eq!(
+            grid.neighbors_in(&corner, Neighborhood::Knight)
+                .collect::<Vec<Index>>(),
+            vec![Index { x: 1, y: 2 }, Index { x: 2, y: 1 }]
+        );
+    }
+
+    #[test]
+    fn custom_offsets() {
+        let grid: Grid<usize> = "123\n456\n789".parse().unwrap();
+        let diagonals = [(-1, -1), (1, 1)];
+        let values = grid
+            .neighbors_in(&Index { x: 1, y: 1 }, Neighborhood::Custom(&diagonals))
+            .map(|i| *grid.get(&i).unwrap())
+            .collect::<Vec<usize>>();
+        assert_eq!(values, vec![1, 9]);
+    }
+}
@@ -0,0 +1,270 @@
+//! Drawing grids as text, ANSI colored text, or PPM and PNG images, with
+//! paths or regions highlighted on top.
+use crate::{Grid, Index};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+pub type Rgb = [u8; 3];
+
+/// A set of cells drawn over a grid with their own glyph and color
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub cells: HashSet<Index>,
+    pub glyph: char,
+    pub color: Rgb,
+}
+
+impl Overlay {
+    pub fn new<I>(cells: I, glyph: char, color: Rgb) -> Self
+    where
+        I: IntoIterator<Item = Index>,
+    {
+        Overlay {
+            cells: cells.into_iter().collect(),
+            glyph,
+            color,
+        }
+    }
+}
+
+/// The topmost overlay covering `index`, later overlays win
+fn overlay_at<'a>(overlays: &'a [Overlay], index: &Index) -> Option<&'a Overlay> {
+    overlays
+        .iter()
+        .rev()
+        .find(|overlay| overlay.cells.contains(index))
+}
+
+/// Blue for 0.0 through green to red for 1.0, clamping anything outside
+pub fn heatmap(value: f64) -> Rgb {
+    let value = value.clamp(0.0, 1.0);
+    let (red, green, blue) = if value < 0.5 {
+        (0.0, value * 2.0, 1.0 - value * 2.0)
+    } else {
+        ((value - 0.5) * 2.0, 1.0 - (value - 0.5) * 2.0, 0.0)
+    };
+    [
+        (red * 255.0).round() as u8,
+        (green * 255.0).round() as u8,
+        (blue * 255.0).round() as u8,
+    ]
+}
+
+impl<T> Grid<T> {
+    /// One char per cell, one line per row
+    pub fn render<G>(&self, glyph: G, overlays: &[Overlay]) -> String
+    where
+        G: Fn(&T) -> char,
+    {
+        let mut rendered = String::with_capacity((self.length + 1) * self.height);
+        for index in self.indices() {
+            rendered.push(match overlay_at(overlays, &index) {
+                Some(overlay) => overlay.glyph,
+                None => glyph(self.get(&index).expect("Index came from the grid")),
+            });
+            if index.x as usize == self.length - 1 {
+                rendered.push('\n');
+            }
+        }
+        rendered
+    }
+
+    /// Like `render`, but every cell also gets a 24 bit ANSI background color
+    pub fn render_ansi<G, C>(&self, glyph: G, color: C, overlays: &[Overlay]) -> String
+    where
+        G: Fn(&T) -> char,
+        C: Fn(&T) -> Rgb,
+    {
+        let mut rendered = String::new();
+        for index in self.indices() {
+            let value = self.get(&index).expect("Index came from the grid");
+            let (glyph, [r, g, b]) = match overlay_at(overlays, &index) {
+                Some(overlay) => (overlay.glyph, overlay.color),
+                None => (glyph(value), color(value)),
+            };
+            write!(rendered, "\x1b[48;2;{};{};{}m{}", r, g, b, glyph)
+                .expect("Writing to a string can't fail");
+            if index.x as usize == self.length - 1 {
+                rendered.push_str("\x1b[0m\n");
+            }
+        }
+        rendered
+    }
+
+    /// Draw each cell as a `scale` by `scale` square of pixels
+    pub fn to_image<C>(&self, color: C, scale: usize, overlays: &[Overlay]) -> Image
+    where
+        C: Fn(&T) -> Rgb,
+    {
+        let width = self.length * scale;
+        let height = self.height * scale;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let index = Index {
+                    x: (x / scale) as isize,
+                    y: (y / scale) as isize,
+                };
+                pixels.push(match overlay_at(overlays, &index) {
+                    Some(overlay) => overlay.color,
+                    None => color(self.get(&index).expect("Index came from the grid")),
+                });
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// An RGB image, stored row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    /// Binary (P6) PPM
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend(self.pixels.iter().flatten());
+        bytes
+    }
+
+    /// An uncompressed PNG, so we don't need a deflate implementation
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(13);
+        header.extend(&(self.width as u32).to_be_bytes());
+        header.extend(&(self.height as u32).to_be_bytes());
+        // 8 bit depth, RGB, default compression, filtering and no interlace
+        header.extend(&[8, 2, 0, 0, 0]);
+
+        // Every row starts with filter type 0 (none)
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(&crc.to_be_bytes());
+}
+
+/// A zlib stream made of stored (uncompressed) deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend(&len.to_le_bytes());
+        stream.extend(&(!len).to_le_bytes());
+        stream.extend(block);
+    }
+    stream.extend(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_text_with_overlay() {
+        let grid: Grid<usize> = "123\n456".parse().unwrap();
+        let path = Overlay::new(
+            vec![Index { x: 0, y: 0 }, Index { x: 1, y: 0 }],
+            '*',
+            [0; 3],
+        );
+        let digit = |value: &usize| std::char::from_digit(*value as u32, 10).unwrap();
+        assert_eq!(grid.render(digit, &[]), "123\n456\n");
+        assert_eq!(grid.render(digit, &[path]), "**3\n456\n");
+    }
+
+    #[test]
+    fn renders_ansi() {
+        let grid: Grid<bool> = "#.".parse().unwrap();
+        let rendered = grid.render_ansi(
+            |_| ' ',
+            |filled| if *filled { [255, 255, 255] } else { [0, 0, 0] },
+            &[],
+        );
+        assert_eq!(
+            rendered,
+            "\x1b[48;2;255;255;255m \x1b[48;2;0;0;0m \x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn heatmap_ends() {
+        assert_eq!(heatmap(0.0), [0, 0, 255]);
+        assert_eq!(heatmap(0.5), [0, 255, 0]);
+        assert_eq!(heatmap(2.0), [255, 0, 0]);
+    }
+
+    #[test]
+    fn writes_images() {
+        let grid: Grid<bool> = "#.".parse().unwrap();
+        let image = grid.to_image(|filled| if *filled { [255; 3] } else { [0; 3] }, 2, &[]);
+        assert_eq!((image.width, image.height), (4, 2));
+
+        let ppm = image.to_ppm();
+        assert!(ppm.starts_with(b"P6\n4 2\n255\n"));
+        assert_eq!(ppm.len(), 11 + 4 * 2 * 3);
+
+        let png = image.to_png();
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G']));
+        // The IEND chunk, including its well known CRC
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
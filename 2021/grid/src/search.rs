@@ -0,0 +1,208 @@
+//! Shortest path searches over grid indices.
+//!
+//! The searches don't hold on to a grid themselves: callers hand in a
+//! neighbor policy (which indices can be reached from an index) and a cost
+//! function (what it costs to step between two of them), so the same engine
+//! works for risk maps, walls, or anything else laid out on a grid.
+use crate::Index;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// The cheapest way found from one of the starts to one of the goals
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub cost: usize,
+    /// Every index visited, starting with a start and ending with a goal
+    pub path: Vec<Index>,
+}
+
+/// Finds the cheapest path from any of `starts` to any of `goals` using
+/// Dijkstra's algorithm.
+///
+/// `cost(from, to)` gives the price of stepping between neighbors, or `None`
+/// if the step isn't allowed.
+pub fn dijkstra<S, N, I, C>(
+    starts: S,
+    goals: &HashSet<Index>,
+    neighbors: N,
+    cost: C,
+) -> Option<SearchResult>
+where
+    S: IntoIterator<Item = Index>,
+    N: Fn(&Index) -> I,
+    I: IntoIterator<Item = Index>,
+    C: Fn(&Index, &Index) -> Option<usize>,
+{
+    a_star(starts, goals, neighbors, cost, |_| 0)
+}
+
+/// Finds the cheapest path from any of `starts` to any of `goals` using A*.
+///
+/// The `heuristic` must never overestimate the remaining cost, otherwise the
+/// result isn't guaranteed to be the cheapest; `manhattan_heuristic` is a
+/// safe choice for orthogonal movement.
+pub fn a_star<S, N, I, C, H>(
+    starts: S,
+    goals: &HashSet<Index>,
+    neighbors: N,
+    cost: C,
+    heuristic: H,
+) -> Option<SearchResult>
+where
+    S: IntoIterator<Item = Index>,
+    N: Fn(&Index) -> I,
+    I: IntoIterator<Item = Index>,
+    C: Fn(&Index, &Index) -> Option<usize>,
+    H: Fn(&Index) -> usize,
+{
+    // Best known cost from a start to each index
+    let mut known = HashMap::new();
+    let mut came_from = HashMap::new();
+    let mut discovered = BinaryHeap::new();
+
+    for start in starts {
+        known.insert(start, 0);
+        discovered.push(Reverse((heuristic(&start), 0, start)));
+    }
+
+    while let Some(Reverse((_, distance, current))) = discovered.pop() {
+        if goals.contains(&current) {
+            return Some(SearchResult {
+                cost: distance,
+                path: reconstruct_path(&came_from, current),
+            });
+        }
+
+        // Stale entry, we've already found a cheaper way here
+        if known.get(&current).is_some_and(|best| distance > *best) {
+            continue;
+        }
+
+        for neighbor in neighbors(&current) {
+            let step = match cost(&current, &neighbor) {
+                Some(step) => step,
+                None => continue,
+            };
+            let tentative = distance + step;
+            if known.get(&neighbor).is_none_or(|best| tentative < *best) {
+                known.insert(neighbor, tentative);
+                came_from.insert(neighbor, current);
+                discovered.push(Reverse((
+                    tentative + heuristic(&neighbor),
+                    tentative,
+                    neighbor,
+                )));
+            }
+        }
+    }
+    None
+}
+
+fn reconstruct_path(came_from: &HashMap<Index, Index>, goal: Index) -> Vec<Index> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while let Some(previous) = came_from.get(&current) {
+        path.push(*previous);
+        current = *previous;
+    }
+    path.reverse();
+    path
+}
+
+/// Manhattan distance to the closest goal, scaled by the cheapest possible
+/// step so it never overestimates.
+pub fn manhattan_heuristic(
+    goals: &HashSet<Index>,
+    min_step_cost: usize,
+) -> impl Fn(&Index) -> usize + '_ {
+    move |index| {
+        goals
+            .iter()
+            .map(|goal| {
+                ((goal.x - index.x).unsigned_abs() + (goal.y - index.y).unsigned_abs())
+                    * min_step_cost
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Grid, Neighborhood};
+
+    fn corners(grid: &Grid<usize>) -> (Index, HashSet<Index>) {
+        let start = Index { x: 0, y: 0 };
+        let goal = Index {
+            x: grid.length as isize - 1,
+            y: grid.height as isize - 1,
+        };
+        (start, HashSet::from([goal]))
+    }
+
+    #[test]
+    fn dijkstra_and_a_star_agree() {
+        let grid: Grid<usize> = "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n\
+                                 1319128137\n1359912421\n3125421639\n1293138521\n2311944581"
+            .parse()
+            .unwrap();
+        let (start, goals) = corners(&grid);
+        let cost = |_: &Index, to: &Index| grid.get(to).copied();
+
+        let dijkstra = dijkstra(
+            [start],
+            &goals,
+            |i| grid.neighbors_in(i, Neighborhood::VonNeumann),
+            cost,
+        )
+        .unwrap();
+        let a_star = a_star(
+            [start],
+            &goals,
+            |i| grid.neighbors_in(i, Neighborhood::VonNeumann),
+            cost,
+            manhattan_heuristic(&goals, 1),
+        )
+        .unwrap();
+
+        assert_eq!(dijkstra.cost, 40);
+        assert_eq!(a_star.cost, 40);
+        assert_eq!(dijkstra.path.first(), Some(&start));
+        assert!(goals.contains(dijkstra.path.last().unwrap()));
+        let path_cost: usize = a_star.path[1..].iter().map(|i| grid.get(i).unwrap()).sum();
+        assert_eq!(path_cost, a_star.cost);
+    }
+
+    #[test]
+    fn respects_impassable_cells() {
+        let grid: Grid<char> = "...\n##.\n...".parse().unwrap();
+        let start = Index { x: 0, y: 0 };
+        let goals = HashSet::from([Index { x: 0, y: 2 }]);
+        let result = dijkstra(
+            [start],
+            &goals,
+            |i| grid.neighbors_in(i, Neighborhood::VonNeumann),
+            |_, to| match grid.get(to) {
+                Some('.') => Some(1),
+                _ => None,
+            },
+        )
+        .unwrap();
+        assert_eq!(result.cost, 6);
+        assert_eq!(result.path.len(), 7);
+    }
+
+    #[test]
+    fn no_path_is_none() {
+        let grid: Grid<char> = ".#.".parse().unwrap();
+        let goals = HashSet::from([Index { x: 2, y: 0 }]);
+        let result = dijkstra(
+            [Index { x: 0, y: 0 }],
+            &goals,
+            |i| grid.neighbors_in(i, Neighborhood::VonNeumann),
+            |_, to| (grid.get(to) == Some(&'.')).then_some(1),
+        );
+        assert_eq!(result, None);
+    }
+}
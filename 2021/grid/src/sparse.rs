@@ -0,0 +1,237 @@
+//! A grid that only stores the cells that have been set, for maps that are
+//! mostly empty, unbounded, or reach into negative coordinates.
+use crate::{BoundingBox, Grid, Index};
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Index, T>,
+    bounds: Option<BoundingBox>,
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+            bounds: None,
+        }
+    }
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The smallest box covering every set cell, or `None` if there aren't any
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, index: &Index) -> bool {
+        self.cells.contains_key(index)
+    }
+
+    pub fn get(&self, index: &Index) -> Option<&T> {
+        self.cells.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: &Index) -> Option<&mut T> {
+        self.cells.get_mut(index)
+    }
+
+    /// Set a cell, returning what was there before
+    pub fn insert(&mut self, index: Index, value: T) -> Option<T> {
+        self.include(&index);
+        self.cells.insert(index, value)
+    }
+
+    /// The value at `index`, setting it to `default()` first if it's empty
+    pub fn get_or_insert_with<F>(&mut self, index: Index, default: F) -> &mut T
+    where
+        F: FnOnce() -> T,
+    {
+        self.include(&index);
+        self.cells.entry(index).or_insert_with(default)
+    }
+
+    /// Clear a cell, shrinking the bounds if it was on the edge
+    pub fn remove(&mut self, index: &Index) -> Option<T> {
+        let removed = self.cells.remove(index)?;
+        if let Some(bounds) = self.bounds {
+            if index.x == bounds.min.x
+                || index.x == bounds.max.x
+                || index.y == bounds.min.y
+                || index.y == bounds.max.y
+            {
+                self.bounds = None;
+                let indices = self.cells.keys().copied().collect::<Vec<Index>>();
+                for index in &indices {
+                    self.include(index);
+                }
+            }
+        }
+        Some(removed)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Index, &T)> {
+        self.cells.iter()
+    }
+
+    fn include(&mut self, index: &Index) {
+        match &mut self.bounds {
+            Some(bounds) => bounds.include(index),
+            None => self.bounds = Some(BoundingBox::from_index(index)),
+        }
+    }
+
+    /// Only keep the cells of `grid` matching `keep`
+    pub fn from_grid<P>(grid: Grid<T>, keep: P) -> Self
+    where
+        P: Fn(&T) -> bool,
+    {
+        let indices = grid.indices().collect::<Vec<Index>>();
+        indices
+            .into_iter()
+            .zip(grid.storage)
+            .filter(|(_, value)| keep(value))
+            .collect()
+    }
+
+    /// Render every cell inside the bounds, one line per row
+    pub fn render<F>(&self, empty: char, cell: F) -> String
+    where
+        F: Fn(&T) -> char,
+    {
+        let mut rendered = String::new();
+        if let Some(bounds) = self.bounds {
+            for y in bounds.min.y..=bounds.max.y {
+                for x in bounds.min.x..=bounds.max.x {
+                    rendered.push(self.get(&Index { x, y }).map_or(empty, &cell));
+                }
+                rendered.push('\n');
+            }
+        }
+        rendered
+    }
+}
+
+impl<T: Clone> SparseGrid<T> {
+    /// Copy into a dense grid covering the bounds, filling gaps with `empty`.
+    /// Cell `(0, 0)` of the result is `bounds().min`.
+    pub fn to_grid(&self, empty: T) -> Grid<T> {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return Grid::filled(0, 0, empty),
+        };
+        let mut grid = Grid::filled(bounds.length(), bounds.height(), empty);
+        for (index, value) in &self.cells {
+            let shifted = Index {
+                x: index.x - bounds.min.x,
+                y: index.y - bounds.min.y,
+            };
+            *grid.get_mut(&shifted).expect("Bounds cover every cell") = value.clone();
+        }
+        grid
+    }
+}
+
+impl<T> FromIterator<(Index, T)> for SparseGrid<T> {
+    fn from_iter<I: IntoIterator<Item = (Index, T)>>(iter: I) -> Self {
+        let mut grid = SparseGrid::new();
+        grid.extend(iter);
+        grid
+    }
+}
+
+impl<T> Extend<(Index, T)> for SparseGrid<T> {
+    fn extend<I: IntoIterator<Item = (Index, T)>>(&mut self, iter: I) {
+        for (index, value) in iter {
+            self.insert(index, value);
+        }
+    }
+}
+
+impl<T> From<Grid<T>> for SparseGrid<T> {
+    fn from(grid: Grid<T>) -> Self {
+        SparseGrid::from_grid(grid, |_| true)
+    }
+}
+
+/// Empty cells are drawn as `.`
+impl<T: fmt::Display> fmt::Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(bounds) = self.bounds {
+            for y in bounds.min.y..=bounds.max.y {
+                for x in bounds.min.x..=bounds.max.x {
+                    match self.get(&Index { x, y }) {
+                        Some(value) => write!(f, "{}", value)?,
+                        None => write!(f, ".")?,
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_bounds_with_negative_coordinates() {
+        let mut grid = SparseGrid::new();
+        assert_eq!(grid.bounds(), None);
+        grid.insert(Index { x: -2, y: 1 }, 'a');
+        grid.insert(Index { x: 3, y: -1 }, 'b');
+        grid.insert(Index { x: 0, y: 0 }, 'c');
+        assert_eq!(
+            grid.bounds(),
+            Some(BoundingBox {
+                min: Index { x: -2, y: -1 },
+                max: Index { x: 3, y: 1 },
+            })
+        );
+
+        grid.remove(&Index { x: 3, y: -1 });
+        assert_eq!(
+            grid.bounds(),
+            Some(BoundingBox {
+                min: Index { x: -2, y: 0 },
+                max: Index { x: 0, y: 1 },
+            })
+        );
+        assert_eq!(grid.to_string(), "..c\na..\n");
+    }
+
+    #[test]
+    fn counts_overlaps() {
+        let mut grid = SparseGrid::new();
+        for x in [0, 1, 1, 2, 1] {
+            *grid.get_or_insert_with(Index { x, y: 0 }, || 0) += 1;
+        }
+        assert_eq!(grid.get(&Index { x: 1, y: 0 }), Some(&3));
+        assert_eq!(grid.iter().filter(|(_, count)| **count >= 2).count(), 1);
+    }
+
+    #[test]
+    fn round_trips_through_dense() {
+        let dense: Grid<bool> = "#..\n..#\n".parse().unwrap();
+        let sparse = SparseGrid::from_grid(dense, |filled| *filled);
+        assert_eq!(sparse.len(), 2);
+        assert_eq!(sparse.render('.', |_| '#'), "#..\n..#\n");
+        assert_eq!(sparse.to_grid(false), "#..\n..#".parse().unwrap());
+    }
+}
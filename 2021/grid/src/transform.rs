@@ -0,0 +1,202 @@
+//! Rotating, reflecting, folding and resizing whole grids. Every transform
+//! returns a new grid and leaves the original alone.
+use crate::{BoundingBox, Grid, Index};
+
+/// A line to fold a grid along. The line itself is dropped, and the far
+/// side is reflected on top of the near side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fold {
+    /// Fold the rows below `y` up
+    AlongRow(usize),
+    /// Fold the columns right of `x` over to the left
+    AlongColumn(usize),
+}
+
+impl<T: Clone> Grid<T> {
+    fn at(&self, x: usize, y: usize) -> &T {
+        &self.storage[y * self.length + x]
+    }
+
+    /// Rotate a quarter turn clockwise
+    pub fn rotate_90(&self) -> Grid<T> {
+        Grid::from_fn(self.height, self.length, |i| {
+            self.at(i.y as usize, self.height - 1 - i.x as usize)
+                .clone()
+        })
+    }
+
+    pub fn rotate_180(&self) -> Grid<T> {
+        Grid::from_fn(self.length, self.height, |i| {
+            self.at(
+                self.length - 1 - i.x as usize,
+                self.height - 1 - i.y as usize,
+            )
+            .clone()
+        })
+    }
+
+    /// Rotate a quarter turn counter clockwise
+    pub fn rotate_270(&self) -> Grid<T> {
+        Grid::from_fn(self.height, self.length, |i| {
+            self.at(self.length - 1 - i.y as usize, i.x as usize)
+                .clone()
+        })
+    }
+
+    /// Swap rows and columns, mirroring along the main diagonal
+    pub fn transpose(&self) -> Grid<T> {
+        Grid::from_fn(self.height, self.length, |i| {
+            self.at(i.y as usize, i.x as usize).clone()
+        })
+    }
+
+    /// Mirror left to right
+    pub fn flip_horizontal(&self) -> Grid<T> {
+        Grid::from_fn(self.length, self.height, |i| {
+            self.at(self.length - 1 - i.x as usize, i.y as usize)
+                .clone()
+        })
+    }
+
+    /// Mirror top to bottom
+    pub fn flip_vertical(&self) -> Grid<T> {
+        Grid::from_fn(self.length, self.height, |i| {
+            self.at(i.x as usize, self.height - 1 - i.y as usize)
+                .clone()
+        })
+    }
+
+    /// Fold the grid in two, combining cells that land on each other with
+    /// `merge(near, far)`. If the far side is longer, the result is aligned
+    /// so the fold line is its last row (or column).
+    pub fn fold<F>(&self, fold: Fold, merge: F) -> Grid<T>
+    where
+        F: Fn(&T, &T) -> T,
+    {
+        match fold {
+            Fold::AlongRow(row) => self.fold_up(row, merge),
+            Fold::AlongColumn(column) => self.transpose().fold_up(column, merge).transpose(),
+        }
+    }
+
+    fn fold_up<F>(&self, row: usize, merge: F) -> Grid<T>
+    where
+        F: Fn(&T, &T) -> T,
+    {
+        let near = row.min(self.height);
+        let far = self.height.saturating_sub(row + 1);
+        let height = near.max(far);
+        let shift = height - near;
+
+        Grid::from_fn(self.length, height, |i| {
+            let (x, y) = (i.x as usize, i.y as usize);
+            let near_cell = y.checked_sub(shift).map(|y| self.at(x, y));
+            let far_cell = (far > height - 1 - y).then(|| self.at(x, row + height - y));
+            match (near_cell, far_cell) {
+                (Some(near), Some(far)) => merge(near, far),
+                (Some(cell), None) | (None, Some(cell)) => cell.clone(),
+                (None, None) => unreachable!("Every row has a near or a far side"),
+            }
+        })
+    }
+
+    /// The part of the grid inside `bounds`, clipped to the grid
+    pub fn crop(&self, bounds: &BoundingBox) -> Grid<T> {
+        let min_x = bounds.min.x.max(0);
+        let min_y = bounds.min.y.max(0);
+        let max_x = bounds.max.x.min(self.length as isize - 1);
+        let max_y = bounds.max.y.min(self.height as isize - 1);
+        let length = (max_x - min_x + 1).max(0) as usize;
+        let height = (max_y - min_y + 1).max(0) as usize;
+        Grid::from_fn(length, height, |i| {
+            self.get(&Index {
+                x: i.x + min_x,
+                y: i.y + min_y,
+            })
+            .expect("Clipped to the grid")
+            .clone()
+        })
+    }
+
+    /// Surround the grid with `amount` cells of `value` on every side
+    pub fn pad(&self, amount: usize, value: T) -> Grid<T> {
+        let amount = amount as isize;
+        Grid::from_fn(
+            self.length + 2 * amount as usize,
+            self.height + 2 * amount as usize,
+            |i| {
+                self.get(&Index {
+                    x: i.x - amount,
+                    y: i.y - amount,
+                })
+                .unwrap_or(&value)
+                .clone()
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(lines: &str) -> Grid<char> {
+        lines.parse().unwrap()
+    }
+
+    #[test]
+    fn rotations() {
+        let original = grid("abc\ndef");
+        assert_eq!(original.rotate_90(), grid("da\neb\nfc"));
+        assert_eq!(original.rotate_180(), grid("fed\ncba"));
+        assert_eq!(original.rotate_270(), grid("cf\nbe\nad"));
+        assert_eq!(original.rotate_90().rotate_270(), original);
+    }
+
+    #[test]
+    fn reflections() {
+        let original = grid("abc\ndef");
+        assert_eq!(original.transpose(), grid("ad\nbe\ncf"));
+        assert_eq!(original.flip_horizontal(), grid("cba\nfed"));
+        assert_eq!(original.flip_vertical(), grid("def\nabc"));
+    }
+
+    #[test]
+    fn folds() {
+        let or = |a: &bool, b: &bool| *a || *b;
+        let paper: Grid<bool> = "#..\n...\n...\n.#.\n..#".parse().unwrap();
+        let folded: Grid<bool> = "#.#\n.#.".parse().unwrap();
+        assert_eq!(paper.fold(Fold::AlongRow(2), or), folded);
+
+        // Folding close to the top leaves the far side longer
+        let folded: Grid<bool> = "..#\n.#.\n...\n...".parse().unwrap();
+        assert_eq!(paper.fold(Fold::AlongRow(0), or), folded);
+
+        let sideways = grid("ab.cd");
+        assert_eq!(
+            sideways.fold(Fold::AlongColumn(2), |a, b| if *a == '.' { *b } else { *a }),
+            grid("ab")
+        );
+        assert_eq!(
+            grid("abc.d").fold(Fold::AlongColumn(3), |a, _| *a),
+            grid("abc")
+        );
+    }
+
+    #[test]
+    fn crop_and_pad() {
+        let original = grid("abc\ndef\nghi");
+        let middle = BoundingBox {
+            min: Index { x: 1, y: 1 },
+            max: Index { x: 5, y: 5 },
+        };
+        assert_eq!(original.crop(&middle), grid("ef\nhi"));
+        assert_eq!(grid("ab").pad(1, '.'), grid("....\n.ab.\n...."));
+        let padded = original.pad(2, '.');
+        let inner = BoundingBox {
+            min: Index { x: 2, y: 2 },
+            max: Index { x: 4, y: 4 },
+        };
+        assert_eq!(padded.crop(&inner), original);
+    }
+}
@@ -0,0 +1,157 @@
+//! Read only views over grid shaped data, so searches and neighbor lookups
+//! work the same whether the cells are stored or computed on the fly.
+use crate::neighborhood::Neighbors;
+use crate::{Grid, Index, Neighborhood};
+
+/// Anything that looks like a `length` by `height` grid of cells
+pub trait GridView {
+    type Cell;
+
+    fn length(&self) -> usize;
+    fn height(&self) -> usize;
+    /// The cell at `index`, or `None` if it's outside the view
+    fn cell(&self, index: &Index) -> Option<Self::Cell>;
+
+    fn contains(&self, index: &Index) -> bool {
+        index.x >= 0
+            && index.y >= 0
+            && (index.x as usize) < self.length()
+            && (index.y as usize) < self.height()
+    }
+
+    /// Neighbors of `index` that are inside the view
+    fn neighbors_in<'a>(&self, index: &Index, neighborhood: Neighborhood<'a>) -> Neighbors<'a> {
+        Neighbors::new(index, neighborhood, self.length(), self.height())
+    }
+
+    /// Every index in the view, row by row
+    fn indices(&self) -> Box<dyn Iterator<Item = Index>> {
+        let length = self.length() as isize;
+        Box::new(
+            (0..self.height() as isize).flat_map(move |y| (0..length).map(move |x| Index { x, y })),
+        )
+    }
+
+    /// Copy the view into a concrete grid
+    fn to_grid(&self) -> Grid<Self::Cell> {
+        Grid {
+            storage: self
+                .indices()
+                .map(|index| self.cell(&index).expect("Index came from the view"))
+                .collect(),
+            length: self.length(),
+            height: self.height(),
+        }
+    }
+}
+
+impl<T: Clone> GridView for Grid<T> {
+    type Cell = T;
+
+    fn length(&self) -> usize {
+        self.length
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn cell(&self, index: &Index) -> Option<T> {
+        self.get(index).cloned()
+    }
+}
+
+/// A grid repeated `tiles_x` by `tiles_y` times without copying it. Each
+/// value is passed through `transform` along with the tile it landed in.
+pub struct Tiled<'a, T, F> {
+    grid: &'a Grid<T>,
+    tiles_x: usize,
+    tiles_y: usize,
+    transform: F,
+}
+
+impl<T> Grid<T> {
+    /// A lazy view of this grid tiled `tiles_x` by `tiles_y` times, where the
+    /// cell in tile `(tx, ty)` is `transform(value, tx, ty)`
+    pub fn tiled<F>(&self, tiles_x: usize, tiles_y: usize, transform: F) -> Tiled<'_, T, F>
+    where
+        F: Fn(&T, usize, usize) -> T,
+    {
+        Tiled {
+            grid: self,
+            tiles_x,
+            tiles_y,
+            transform,
+        }
+    }
+}
+
+impl<T, F> GridView for Tiled<'_, T, F>
+where
+    F: Fn(&T, usize, usize) -> T,
+{
+    type Cell = T;
+
+    fn length(&self) -> usize {
+        self.grid.length * self.tiles_x
+    }
+
+    fn height(&self) -> usize {
+        self.grid.height * self.tiles_y
+    }
+
+    fn cell(&self, index: &Index) -> Option<T> {
+        if !self.contains(index) {
+            return None;
+        }
+        let (x, y) = (index.x as usize, index.y as usize);
+        let inner = Index {
+            x: (x % self.grid.length) as isize,
+            y: (y % self.grid.height) as isize,
+        };
+        self.grid
+            .get(&inner)
+            .map(|value| (self.transform)(value, x / self.grid.length, y / self.grid.height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search;
+    use std::collections::HashSet;
+
+    #[test]
+    fn tiles_with_transform() {
+        let grid: Grid<usize> = "12\n34".parse().unwrap();
+        let tiled = grid.tiled(2, 2, |value, tx, ty| value + 10 * (tx + ty));
+        assert_eq!(tiled.length(), 4);
+        assert_eq!(tiled.height(), 4);
+        assert_eq!(tiled.cell(&Index { x: 4, y: 0 }), None);
+        assert_eq!(
+            tiled.to_grid().storage,
+            vec![1, 2, 11, 12, 3, 4, 13, 14, 11, 12, 21, 22, 13, 14, 23, 24]
+        );
+    }
+
+    #[test]
+    fn search_on_a_view() {
+        let grid: Grid<usize> = "19\n11".parse().unwrap();
+        let tiled = grid.tiled(3, 1, |value, _, _| *value);
+        let goals = HashSet::from([Index { x: 5, y: 1 }]);
+        let result = search::dijkstra(
+            [Index { x: 0, y: 0 }],
+            &goals,
+            |i| tiled.neighbors_in(i, Neighborhood::VonNeumann),
+            |_, to| tiled.cell(to),
+        )
+        .unwrap();
+        assert_eq!(result.cost, 6);
+        assert_eq!(
+            tiled
+                .neighbors_in(&Index { x: 5, y: 1 }, Neighborhood::Moore)
+                .count(),
+            3
+        );
+    }
+}
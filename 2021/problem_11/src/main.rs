@@ -1,113 +1,78 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use env_logger::Env;
-use grid::Grid;
-use log::info;
-use std::collections::HashSet;
+use grid::{Automaton, Control, CycleDetector, Grid, Neighborhood};
 
-#[derive(Debug, Hash, PartialEq, Eq)]
-struct Index {
-    x: isize,
-    y: isize,
+/// Every octopus gains energy, then the ones over 9 flash, feeding their
+/// neighbors. Returns how many flashed this step.
+fn step(octopuses: &mut Automaton<usize>) -> usize {
+    octopuses
+        .grid
+        .storage
+        .iter_mut()
+        .for_each(|energy| *energy += 1);
+    let flashed = octopuses.cascade(|energy| *energy > 9, |energy| *energy += 1);
+    for index in &flashed {
+        *octopuses.grid.get_mut(index).unwrap() = 0;
+    }
+    flashed.len()
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
-enum SimulationGoal {
-    TotalFlash,
-    SimultaneousSearch,
+fn total_flashes(steps: usize, grid: Grid<usize>) -> usize {
+    let mut flash_count = 0;
+    Automaton::new(grid, Neighborhood::Moore).run(steps, step, |_, _, flashed| {
+        flash_count += flashed;
+        Control::Continue
+    });
+    flash_count
 }
 
-fn simulate(steps: usize, mut grid: Grid, goal: SimulationGoal) -> usize {
-    let mut flash_count = 0;
-    for step in 0..steps {
-        let mut to_flash = Vec::new();
-        for x in 0..grid.length {
-            for y in 0..grid.height {
-                // Increase octopus
-                *grid.access_mut(x as isize, y as isize).unwrap() += 1;
-                if grid.access(x as isize, y as isize).unwrap() > 9 {
-                    to_flash.push((x as isize, y as isize));
-                }
+/// The first step where every octopus flashes at once, or `None` if they
+/// settle into a cycle without ever doing so
+fn first_simultaneous_flash(grid: Grid<usize>) -> Option<usize> {
+    let mut seen = CycleDetector::new();
+    let mut synchronized = false;
+    let stopped =
+        Automaton::new(grid, Neighborhood::Moore).run(usize::MAX, step, |octopuses, _, flashed| {
+            synchronized = flashed == octopuses.grid.storage.len();
+            if synchronized || seen.observe(&octopuses.grid).is_some() {
+                Control::Stop
+            } else {
+                Control::Continue
             }
-        }
-        let mut flashed = HashSet::new();
-        // Process flashes
-        while let Some((x, y)) = to_flash.pop() {
-            let start = Index { x, y };
-            if flashed.contains(&start) {
-                continue;
-            }
-            flashed.insert(start);
-            flash_count += 1;
-            for x_dif in -1..2 {
-                for y_dif in -1..2 {
-                    let index = Index {
-                        x: x + x_dif,
-                        y: y + y_dif,
-                    };
-                    if !flashed.contains(&index) {
-                        if let Some(value) = grid.access_mut(x + x_dif, y + y_dif) {
-                            *value += 1;
-                            if *value > 9 {
-                                to_flash.push((x + x_dif, y + y_dif))
-                            }
-                        }
-                    }
-                }
-            }
-        }
-	let flashed_this_step = flashed.len();
-        flashed.into_iter().for_each(|index| {
-            *grid.access_mut(index.x, index.y).unwrap() = 0;
         });
-        if let (SimulationGoal::SimultaneousSearch, 100) = (goal, flashed_this_step) {
-            return step;
-        } 
-    }
-    flash_count
+    stopped.filter(|_| synchronized)
 }
 
 fn main() -> Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
-    let test_grid = Grid::from_vec_str(
-        5,
-        5,
-        vec!["11111", "19991", "19191", "19991", "11111"]
-            .iter()
-            .map(|x| x.to_string())
-            .collect(),
-    )?;
-    let test_flash_count = simulate(2, test_grid, SimulationGoal::TotalFlash);
+    let test_grid = Grid::from_lines(["11111", "19991", "19191", "19991", "11111"])?;
+    let test_flash_count = total_flashes(2, test_grid);
     assert_eq!(test_flash_count, 9);
 
-    let test_grid_2 = Grid::from_vec_str(
-        10,
-        10,
-        vec![
-            "5483143223",
-            "2745854711",
-            "5264556173",
-            "6141336146",
-            "6357385478",
-            "4167524645",
-            "2176841721",
-            "6882881134",
-            "4846848554",
-            "5283751526",
-        ]
-        .iter()
-        .map(|x| x.to_string())
-        .collect(),
-    )?;
+    let test_grid_2 = Grid::from_lines([
+        "5483143223",
+        "2745854711",
+        "5264556173",
+        "6141336146",
+        "6357385478",
+        "4167524645",
+        "2176841721",
+        "6882881134",
+        "4846848554",
+        "5283751526",
+    ])?;
 
-    let test_sim_step = simulate(200, test_grid_2, SimulationGoal::SimultaneousSearch);
-    assert_eq!(test_sim_step + 1, 195);
+    assert_eq!(total_flashes(100, test_grid_2.clone()), 1656);
+    let test_sim_step = first_simultaneous_flash(test_grid_2);
+    assert_eq!(test_sim_step, Some(195));
 
-    let mut grid = Grid::from_stdin(10, 10)?;
-    // let flash_count = simulate(100, grid, SimulationGoal::TotalFlash);
+    let grid = Grid::from_stdin()?;
+    // let flash_count = total_flashes(100, grid);
 
-    let sim_step = simulate(2000, grid, SimulationGoal::SimultaneousSearch);
+    let sim_step = first_simultaneous_flash(grid)
+        .ok_or_else(|| anyhow!("Octopuses never flashed together"))?;
 
-    println!("sim_step: {}", sim_step + 1);
+    println!("sim_step: {}", sim_step);
     Ok(())
 }
@@ -1,56 +1,23 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use env_logger::Env;
+use grid::{Fold, Grid, Index, SparseGrid};
 use log::info;
-use std::collections::HashSet;
 use std::fmt;
 use std::io;
 use std::io::BufRead;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-struct Point {
-    x: isize,
-    y: isize,
-}
-
-#[derive(Debug, Clone, Copy)]
-enum Reflection {
-    X(isize),
-    Y(isize),
-}
-
 #[derive(Debug)]
 struct Graph {
     /// Stack of reflections
-    reflections: Vec<Reflection>,
-    /// Points in the graph
-    points: HashSet<Point>,
+    reflections: Vec<Fold>,
+    /// The transparent paper, `true` where there's a dot
+    paper: Grid<bool>,
 }
 
 impl fmt::Display for Graph {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let max_x = self
-            .points
-            .iter()
-            .max_by(|point, point2| point.x.cmp(&point2.x))
-            .unwrap()
-            .x;
-        let max_y = self
-            .points
-            .iter()
-            .max_by(|point, point2| point.y.cmp(&point2.y))
-            .unwrap()
-            .y;
-
-        Ok(for y in 0..max_y + 1 {
-            write!(f, "\n");
-            for x in 0..max_x + 1 {
-                if self.points.contains(&Point { x, y }) {
-                    write!(f, "#");
-                } else {
-                    write!(f, ".");
-                }
-            }
-        })
+        let rendered = self.paper.render(|dot| if *dot { '#' } else { '.' }, &[]);
+        write!(f, "\n{}", rendered)
     }
 }
 
@@ -60,32 +27,33 @@ impl Graph {
         let handle = stdin.lock();
         let lines = handle.lines();
 
-        Ok(Self::from_vec_str(
-            lines.into_iter().map(|x| x.unwrap()).collect(),
-        ))
+        Ok(Self::from_vec_str(lines.map(|x| x.unwrap()).collect()))
     }
 
     fn from_vec_str(data: Vec<String>) -> Self {
-        let mut points = HashSet::new();
+        let mut points = SparseGrid::new();
         let mut reflections = Vec::new();
         let mut i = 0;
         while i < data.len() {
             let entry = &data[i];
             i += 1;
-            if entry == "" {
+            if entry.is_empty() {
                 break;
             }
 
-            let values = entry.split(",").collect::<Vec<&str>>();
-            points.insert(Point {
-                x: values[0].parse::<isize>().expect("x"),
-                y: values[1].parse::<isize>().expect("y"),
-            });
+            let values = entry.split(',').collect::<Vec<&str>>();
+            points.insert(
+                Index {
+                    x: values[0].parse::<isize>().expect("x"),
+                    y: values[1].parse::<isize>().expect("y"),
+                },
+                (),
+            );
         }
 
         while i < data.len() {
             let reflection = &data[i];
-            let mut values = reflection.split("=");
+            let mut values = reflection.split('=');
             let axis = values
                 .next()
                 .expect("Axis")
@@ -94,70 +62,46 @@ impl Graph {
             let integral = values
                 .next()
                 .expect("have value")
-                .parse::<isize>()
+                .parse::<usize>()
                 .expect("Should be integral");
 
             reflections.push(match axis {
-                "x" => Reflection::X(integral),
-                "y" => Reflection::Y(integral),
+                "x" => Fold::AlongColumn(integral),
+                "y" => Fold::AlongRow(integral),
                 _ => panic!("unknown reflection found"),
             });
             i += 1;
         }
-        let reflections = reflections.into_iter().rev().collect::<Vec<Reflection>>();
-        Self {
-            reflections,
-            points,
+        let reflections = reflections.into_iter().rev().collect::<Vec<Fold>>();
+
+        // The paper reaches from the origin to the furthest dot
+        let (length, height) = match points.bounds() {
+            Some(bounds) => (bounds.max.x as usize + 1, bounds.max.y as usize + 1),
+            None => (0, 0),
+        };
+        let mut paper = Grid::filled(length, height, false);
+        for (point, _) in points.iter() {
+            *paper.get_mut(point).expect("Paper covers every dot") = true;
         }
+        Self { reflections, paper }
     }
 
-    fn transform(reflection: Reflection, point: Point) -> Point {
-        match reflection {
-            Reflection::X(integral) => {
-                if point.x > integral {
-                    Point {
-                        x: integral - (point.x - integral),
-                        y: point.y,
-                    }
-                } else {
-                    point
-                }
-            }
-            Reflection::Y(integral) => {
-                if point.y > integral {
-                    Point {
-                        x: point.x,
-                        y: integral - (point.y - integral),
-                    }
-                } else {
-                    point
-                }
-            }
-        }
+    fn dot_count(&self) -> usize {
+        self.paper.storage.iter().filter(|dot| **dot).count()
     }
 
     /// Pop one of the reflections off of the graph and update the points involved
     fn fold(&mut self) -> Option<String> {
-        let reflection = self.reflections.pop();
-        let reflection = match reflection {
-            Some(r) => r,
-            None => return None,
-        };
+        let reflection = self.reflections.pop()?;
 
-        let points = self
-            .points
-            .iter()
-            .cloned()
-            .map(|point| Graph::transform(reflection, point))
-            .collect::<HashSet<Point>>();
-        self.points = points;
+        self.paper = self.paper.fold(reflection, |near, far| *near || *far);
         Some(format!("Completed fold {:?}", reflection))
     }
 }
 
 fn main() -> Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let testing_list = vec!["1,1", "1,5", "", "fold along y=3"];
+    let testing_list = ["1,1", "1,5", "", "fold along y=3"];
     let mut testing_graph = Graph::from_vec_str(
         testing_list
             .iter()
@@ -166,13 +110,13 @@ fn main() -> Result<()> {
     );
     testing_graph.fold();
 
-    assert!(testing_graph.points.contains(&Point { x: 1, y: 1 }));
-    assert_eq!(testing_graph.points.len(), 1);
+    assert_eq!(testing_graph.paper.get(&Index { x: 1, y: 1 }), Some(&true));
+    assert_eq!(testing_graph.dot_count(), 1);
 
     let mut graph = Graph::from_stdin()?;
     graph.fold();
-    info!("Part 1: {}", graph.points.len());
-    while let Some(_) = graph.fold() {}
+    info!("Part 1: {}", graph.dot_count());
+    while graph.fold().is_some() {}
     println!("{}", graph);
     Ok(())
 }
@@ -1,7 +1,12 @@
 use anyhow::{anyhow, Result};
 use env_logger::Env;
+use std::fmt;
 use std::io;
-use std::iter::{FromIterator, IntoIterator};
+use structopt::StructOpt;
+
+/// How deeply packets may nest, so parsing, evaluating and encoding, which
+/// all recurse, can't run out of stack
+const MAX_DEPTH: usize = 512;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum Packet {
@@ -26,39 +31,205 @@ enum Operator {
     Equal,
 }
 
+impl Operator {
+    fn code(&self) -> usize {
+        match self {
+            Operator::Sum => 0,
+            Operator::Product => 1,
+            Operator::Min => 2,
+            Operator::Max => 3,
+            Operator::Gt => 5,
+            Operator::Lt => 6,
+            Operator::Equal => 7,
+        }
+    }
+
+    /// Whether the operator makes sense with this many subpackets
+    fn takes(&self, subpackets: usize) -> bool {
+        match self {
+            Operator::Sum | Operator::Product => true,
+            Operator::Min | Operator::Max => subpackets > 0,
+            Operator::Gt | Operator::Lt | Operator::Equal => subpackets == 2,
+        }
+    }
+}
+
+/// How an operator says where its subpackets end
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LengthType {
+    /// Followed by the total length of the subpackets in 15 bits
+    Bits,
+    /// Followed by the number of subpackets in 11 bits
+    Count,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct PacketOperator {
     packet_version: usize,
     packet_operator_type: Operator,
+    length_type: LengthType,
     subpackets: Vec<Packet>,
 }
 
-fn convert_to_numeric_value(values: Vec<char>) -> Result<usize> {
-    Ok(usize::from_str_radix(&String::from_iter(values.iter()), 2)?)
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum BitsErrorKind {
+    NotHex(char),
+    /// A field wanted more bits than were left
+    Truncated {
+        wanted: usize,
+        left: usize,
+    },
+    LiteralTooLarge,
+    /// Subpackets with a length in bits went past the end of it
+    Overrun {
+        end: usize,
+    },
+    WrongArity {
+        operator: Operator,
+        subpackets: usize,
+    },
+    /// Evaluating the operator overflowed a usize
+    Overflow {
+        operator: Operator,
+    },
+    /// Packets nested past `MAX_DEPTH`
+    TooDeep {
+        depth: usize,
+    },
+    /// A value to encode needs more bits than its field has
+    FieldTooWide {
+        value: usize,
+        width: usize,
+    },
+}
+
+/// Where and why a transmission couldn't be parsed, evaluated or encoded
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct BitsError {
+    /// Bits into the transmission, if the error came from reading or
+    /// writing it
+    offset: Option<usize>,
+    /// Index of each subpacket on the way down from the outermost packet
+    path: Vec<usize>,
+    kind: BitsErrorKind,
+}
+
+impl fmt::Display for BitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let packet = if self.path.is_empty() {
+            "the outermost packet".to_string()
+        } else {
+            let path = self
+                .path
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>();
+            format!("subpacket {}", path.join("."))
+        };
+        match self.offset {
+            Some(offset) => write!(f, "Bit {} of {}: ", offset, packet)?,
+            None => write!(f, "In {}: ", packet)?,
+        }
+        match &self.kind {
+            BitsErrorKind::NotHex(c) => write!(f, "expected a hex digit, found {:?}", c),
+            BitsErrorKind::Truncated { wanted, left } => {
+                write!(f, "wanted {} bits, but only {} are left", wanted, left)
+            }
+            BitsErrorKind::LiteralTooLarge => {
+                write!(f, "literal doesn't fit in {} bits", usize::BITS)
+            }
+            BitsErrorKind::Overrun { end } => write!(
+                f,
+                "subpackets ran past the end of their length at bit {}",
+                end
+            ),
+            BitsErrorKind::WrongArity {
+                operator,
+                subpackets,
+            } => write!(f, "{:?} can't take {} subpackets", operator, subpackets),
+            BitsErrorKind::Overflow { operator } => {
+                write!(f, "{:?} overflows {} bits", operator, usize::BITS)
+            }
+            BitsErrorKind::TooDeep { depth } => {
+                write!(f, "packets nest {} deep, past {}", depth, MAX_DEPTH)
+            }
+            BitsErrorKind::FieldTooWide { value, width } => {
+                write!(f, "{} doesn't fit in {} bits", value, width)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BitsError {}
+
+/// Reads a transmission a few bits at a time, most significant bit first
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// How many bits have been read so far
+    position: usize,
+    /// How many bits there are, which may stop short of the last byte
+    len: usize,
+    /// The subpacket being read, for errors
+    path: Vec<usize>,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8], len: usize) -> Self {
+        BitReader {
+            bytes,
+            position: 0,
+            len: len.min(bytes.len() * 8),
+            path: Vec::new(),
+        }
+    }
+
+    fn error(&self, offset: usize, kind: BitsErrorKind) -> BitsError {
+        BitsError {
+            offset: Some(offset),
+            path: self.path.clone(),
+            kind,
+        }
+    }
+
+    fn read(&mut self, width: usize) -> Result<usize, BitsError> {
+        let left = self.len - self.position;
+        if width > left {
+            return Err(self.error(
+                self.position,
+                BitsErrorKind::Truncated {
+                    wanted: width,
+                    left,
+                },
+            ));
+        }
+        let mut value = 0;
+        for _ in 0..width {
+            let bit = self.bytes[self.position / 8] >> (7 - self.position % 8) & 1;
+            value = value << 1 | bit as usize;
+            self.position += 1;
+        }
+        Ok(value)
+    }
 }
 
 fn parse_packet_literal(
     packet_version: usize,
-    vals: &mut impl Iterator<Item = char>,
-) -> Result<PacketLiteral> {
-    let mut values = Vec::new();
+    bits: &mut BitReader,
+) -> Result<PacketLiteral, BitsError> {
+    let start = bits.position;
+    let mut value: usize = 0;
     loop {
-        let starting_bit = usize::from_str_radix(&String::from_iter(vals.take(1)), 2)?;
-        let mut numerical_bits = vals.take(4).collect::<Vec<char>>();
-        values.append(&mut numerical_bits);
-        match starting_bit {
-            0 => {
-                break;
-            }
-            1 => {}
-            _ => {
-                return Err(anyhow!("Invalid binary"));
-            }
+        let starting_bit = bits.read(1)?;
+        value = value
+            .checked_mul(16)
+            .ok_or_else(|| bits.error(start, BitsErrorKind::LiteralTooLarge))?
+            | bits.read(4)?;
+        if starting_bit == 0 {
+            break;
         }
     }
-    let padding_len = 4 - (values.len() + (values.len() / 4) + 6) % 4;
-    vals.skip(padding_len);
-    let value = convert_to_numeric_value(values)?;
+    // Only the outermost packet is padded, and the padding is just left
+    // unread
     Ok(PacketLiteral {
         value,
         packet_version,
@@ -68,11 +239,10 @@ fn parse_packet_literal(
 fn parse_packet_operator(
     packet_version: usize,
     opcode: usize,
-    vals: &mut impl Iterator<Item = char>,
-) -> Result<PacketOperator> {
-    let packet_length = usize::from_str_radix(&String::from_iter(vals.take(1)), 2)?;
-    let mut packets = Vec::new();
-
+    bits: &mut BitReader,
+) -> Result<PacketOperator, BitsError> {
+    // Past the version and type
+    let start = bits.position - 6;
     let operator = match opcode {
         0 => Operator::Sum,
         1 => Operator::Product,
@@ -81,56 +251,335 @@ fn parse_packet_operator(
         5 => Operator::Gt,
         6 => Operator::Lt,
         7 => Operator::Equal,
-        _ => return Err(anyhow!("unknown opcode")),
+        _ => unreachable!("Every other 3 bit type is a literal or an operator"),
     };
 
-    match packet_length {
+    let mut packets = Vec::new();
+    let length_type = match bits.read(1)? {
         0 => {
-            let packet_bit_length = usize::from_str_radix(&String::from_iter(vals.take(15)), 2)?;
-            let sub_packet_buffer = vals.take(packet_bit_length).collect::<Vec<char>>();
-            let mut sub_packet_iter = sub_packet_buffer.into_iter().peekable();
-
-            while sub_packet_iter.peek() != None {
-                packets.push(parse_packet(sub_packet_iter.by_ref())?);
+            let packet_bit_length = bits.read(15)?;
+            let end = bits.position + packet_bit_length;
+            while bits.position < end {
+                bits.path.push(packets.len());
+                packets.push(parse_packet(bits)?);
+                bits.path.pop();
             }
-        }
-        1 => {
-            // Parse the next 11 packets
-            let num_packets = usize::from_str_radix(&String::from_iter(vals.take(11)), 2)?;
-            for _ in 0..num_packets {
-                packets.push(parse_packet(vals)?);
+            if bits.position != end {
+                return Err(bits.error(bits.position, BitsErrorKind::Overrun { end }));
             }
+            LengthType::Bits
         }
         _ => {
-            return Err(anyhow!("Invalid binary"));
+            let num_packets = bits.read(11)?;
+            for index in 0..num_packets {
+                bits.path.push(index);
+                packets.push(parse_packet(bits)?);
+                bits.path.pop();
+            }
+            LengthType::Count
         }
     };
 
+    if !operator.takes(packets.len()) {
+        return Err(bits.error(
+            start,
+            BitsErrorKind::WrongArity {
+                operator,
+                subpackets: packets.len(),
+            },
+        ));
+    }
     Ok(PacketOperator {
         subpackets: packets,
         packet_operator_type: operator,
+        length_type,
         packet_version,
     })
 }
 
-fn parse_packet(vals: &mut impl Iterator<Item = char>) -> Result<Packet> {
-    let version = String::from_iter(vals.take(3));
-    let packet_version = usize::from_str_radix(&version, 2)?;
-    let packet_type = usize::from_str_radix(&String::from_iter(vals.take(3)), 2)?;
+fn parse_packet(bits: &mut BitReader) -> Result<Packet, BitsError> {
+    let depth = bits.path.len();
+    if depth > MAX_DEPTH {
+        return Err(bits.error(bits.position, BitsErrorKind::TooDeep { depth }));
+    }
+    let packet_version = bits.read(3)?;
+    let packet_type = bits.read(3)?;
     let packet = match packet_type {
-        4 => Packet::Literal(parse_packet_literal(packet_version, vals)?),
-        opcode => Packet::Operator(parse_packet_operator(packet_version, opcode, vals)?),
+        4 => Packet::Literal(parse_packet_literal(packet_version, bits)?),
+        opcode => Packet::Operator(parse_packet_operator(packet_version, opcode, bits)?),
     };
     Ok(packet)
 }
 
-fn from_literal(value: String) -> Result<Packet> {
-    parse_packet(value.chars().by_ref())
+/// Parse a packet written out as '0' and '1' characters
+#[cfg(test)]
+fn from_literal(value: String) -> Result<Packet, BitsError> {
+    let mut bytes = vec![0; value.len().div_ceil(8)];
+    for (offset, c) in value.chars().enumerate() {
+        match c {
+            '0' => {}
+            '1' => bytes[offset / 8] |= 0x80 >> (offset % 8),
+            _ => panic!("Not a bit"),
+        }
+    }
+    parse_packet(&mut BitReader::new(&bytes, value.len()))
+}
+
+/// Pack hex digits two to a byte, returning the bytes and how many bits
+/// they hold. Either case is fine, and surrounding whitespace is ignored.
+fn decode_hex(hex: &str) -> Result<(Vec<u8>, usize), BitsError> {
+    let mut bytes = Vec::new();
+    let mut len = 0;
+    for c in hex.trim().chars() {
+        let digit = c.to_digit(16).ok_or(BitsError {
+            offset: Some(len),
+            path: Vec::new(),
+            kind: BitsErrorKind::NotHex(c),
+        })?;
+        if len % 8 == 0 {
+            bytes.push((digit as u8) << 4);
+        } else {
+            *bytes.last_mut().expect("Pushed a byte for the first digit") |= digit as u8;
+        }
+        len += 4;
+    }
+    Ok((bytes, len))
+}
+
+fn from_hex(hex: &str) -> Result<Packet, BitsError> {
+    let (bytes, len) = decode_hex(hex)?;
+    parse_packet(&mut BitReader::new(&bytes, len))
+}
+
+/// Writes a transmission a few bits at a time, most significant bit first,
+/// for `BitReader` to read back
+#[derive(Debug)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// How many bits have been written, which may stop short of the last byte
+    len: usize,
+    /// The subpacket being written, for errors
+    path: Vec<usize>,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            len: 0,
+            path: Vec::new(),
+        }
+    }
+
+    fn error(&self, offset: usize, kind: BitsErrorKind) -> BitsError {
+        BitsError {
+            offset: Some(offset),
+            path: self.path.clone(),
+            kind,
+        }
+    }
+
+    fn write(&mut self, value: usize, width: usize) -> Result<(), BitsError> {
+        self.write_at(self.len, value, width)
+    }
+
+    /// Write a field starting at `offset`, which may overwrite bits already
+    /// written
+    fn write_at(&mut self, offset: usize, value: usize, width: usize) -> Result<(), BitsError> {
+        if width < usize::BITS as usize && value >> width != 0 {
+            return Err(self.error(offset, BitsErrorKind::FieldTooWide { value, width }));
+        }
+        self.len = self.len.max(offset + width);
+        self.bytes.resize(self.len.div_ceil(8), 0);
+        for (position, shift) in (offset..offset + width).zip((0..width).rev()) {
+            let mask = 0x80 >> (position % 8);
+            if (value >> shift) & 1 == 1 {
+                self.bytes[position / 8] |= mask;
+            } else {
+                self.bytes[position / 8] &= !mask;
+            }
+        }
+        Ok(())
+    }
+
+    fn reader(&self) -> BitReader<'_> {
+        BitReader::new(&self.bytes, self.len)
+    }
+
+    /// The transmission in hex, padding the bits with zeroes to a whole digit
+    fn to_hex(&self) -> String {
+        (0..self.len.div_ceil(4))
+            .map(|digit| {
+                let byte = self.bytes[digit / 2];
+                let nibble = if digit % 2 == 0 {
+                    byte >> 4
+                } else {
+                    byte & 0xf
+                };
+                format!("{:X}", nibble)
+            })
+            .collect()
+    }
+}
+
+/// Write the packet out in bits, the inverse of `parse_packet`
+fn write_packet(bits: &mut BitWriter, packet: &Packet) -> Result<(), BitsError> {
+    let start = bits.len;
+    match packet {
+        Packet::Literal(literal) => {
+            bits.write(literal.packet_version, 3)?;
+            bits.write(4, 3)?;
+            // Groups of 4 bits, most significant first, each starting with a
+            // 1 if there's another group after it
+            let significant_bits = (usize::BITS - literal.value.leading_zeros()) as usize;
+            let groups = significant_bits.div_ceil(4).max(1);
+            for group in (0..groups).rev() {
+                bits.write((group != 0) as usize, 1)?;
+                bits.write((literal.value >> (4 * group)) & 0xf, 4)?;
+            }
+        }
+        Packet::Operator(operator) => {
+            let subpackets = operator.subpackets.len();
+            if !operator.packet_operator_type.takes(subpackets) {
+                return Err(bits.error(
+                    start,
+                    BitsErrorKind::WrongArity {
+                        operator: operator.packet_operator_type.clone(),
+                        subpackets,
+                    },
+                ));
+            }
+            bits.write(operator.packet_version, 3)?;
+            bits.write(operator.packet_operator_type.code(), 3)?;
+            let length_at = bits.len + 1;
+            match operator.length_type {
+                LengthType::Bits => {
+                    bits.write(0, 1)?;
+                    // Filled in once the subpackets are written
+                    bits.write(0, 15)?;
+                }
+                LengthType::Count => {
+                    bits.write(1, 1)?;
+                    bits.write(subpackets, 11)?;
+                }
+            }
+            for (index, subpacket) in operator.subpackets.iter().enumerate() {
+                bits.path.push(index);
+                write_packet(bits, subpacket)?;
+                bits.path.pop();
+            }
+            if operator.length_type == LengthType::Bits {
+                bits.write_at(length_at, bits.len - length_at - 15, 15)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_packet(packet: &Packet) -> Result<BitWriter, BitsError> {
+    let mut bits = BitWriter::new();
+    write_packet(&mut bits, packet)?;
+    Ok(bits)
+}
+
+fn add_versions(packet: &Packet) -> usize {
+    match packet {
+        Packet::Literal(literal) => literal.packet_version,
+        Packet::Operator(op) => {
+            let mut total = op.packet_version;
+            for subpacket in &op.subpackets {
+                total += add_versions(subpacket);
+            }
+            total
+        }
+    }
+}
+
+/// The value of the packet, or where the arithmetic overflowed
+fn evaluate_packet(packet: &Packet) -> Result<usize, BitsError> {
+    evaluate_subpacket(packet, &mut Vec::new())
+}
+
+/// Evaluate a packet found by following `path` from the outermost one
+fn evaluate_subpacket(packet: &Packet, path: &mut Vec<usize>) -> Result<usize, BitsError> {
+    let operator = match packet {
+        Packet::Literal(literal) => return Ok(literal.value),
+        Packet::Operator(operator) => operator,
+    };
+    let mut values = Vec::new();
+    for (index, subpacket) in operator.subpackets.iter().enumerate() {
+        path.push(index);
+        values.push(evaluate_subpacket(subpacket, path)?);
+        path.pop();
+    }
+    let error = |kind| BitsError {
+        offset: None,
+        path: path.clone(),
+        kind,
+    };
+    let overflow = || {
+        error(BitsErrorKind::Overflow {
+            operator: operator.packet_operator_type.clone(),
+        })
+    };
+    Ok(match (&operator.packet_operator_type, values.as_slice()) {
+        (Operator::Sum, values) => values
+            .iter()
+            .try_fold(0usize, |total, value| total.checked_add(*value))
+            .ok_or_else(overflow)?,
+        (Operator::Product, values) => values
+            .iter()
+            .try_fold(1usize, |total, value| total.checked_mul(*value))
+            .ok_or_else(overflow)?,
+        (Operator::Min, [first, rest @ ..]) => rest.iter().copied().fold(*first, usize::min),
+        (Operator::Max, [first, rest @ ..]) => rest.iter().copied().fold(*first, usize::max),
+        (Operator::Gt, [left, right]) => (left > right) as usize,
+        (Operator::Lt, [left, right]) => (left < right) as usize,
+        (Operator::Equal, [left, right]) => (left == right) as usize,
+        // Only packets built by hand get here, since parsing checks arity
+        (operator, values) => {
+            return Err(error(BitsErrorKind::WrongArity {
+                operator: operator.clone(),
+                subpackets: values.len(),
+            }))
+        }
+    })
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "Problem16", about = "Solving AOC problem 16.")]
+struct Args {
+    /// Also print the transmission encoded back from the parsed packet
+    #[structopt(long)]
+    encode: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::from_args();
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let mut buffer = String::new();
+    io::stdin().read_line(&mut buffer)?;
+
+    let packet = from_hex(&buffer)?;
+    if args.encode {
+        let bits = encode_packet(&packet)?;
+        if parse_packet(&mut bits.reader())? != packet {
+            return Err(anyhow!("The encoded packet doesn't parse back the same"));
+        }
+        println!("Encoded: {}", bits.to_hex());
+    }
+    println!("Versions: {}", add_versions(&packet));
+
+    let result = evaluate_packet(&packet)?;
+    println!("Result: {}", result);
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use log::{info, LevelFilter};
     fn init() {
         let _ = env_logger::builder()
             .is_test(true)
@@ -165,7 +614,8 @@ mod tests {
             packet,
             Packet::Operator(PacketOperator {
                 packet_version: 1,
-                packet_operator_type: Operator::Gt,
+                packet_operator_type: Operator::Lt,
+                length_type: LengthType::Bits,
                 subpackets: vec!(
                     Packet::Literal(PacketLiteral {
                         packet_version: 6,
@@ -180,77 +630,254 @@ mod tests {
         );
         Ok(())
     }
-}
 
-fn add_versions(packet: &Packet) -> usize {
-    match packet {
-        Packet::Literal(literal) => literal.packet_version,
-        Packet::Operator(op) => {
-            let mut total = op.packet_version;
-            for subpacket in &op.subpackets {
-                total += add_versions(subpacket);
-            }
-            total
-        }
+    #[test]
+    fn encode_round_trip() -> Result<()> {
+        init();
+        let literal = |packet_version, value| {
+            Packet::Literal(PacketLiteral {
+                packet_version,
+                value,
+            })
+        };
+        let packet = Packet::Operator(PacketOperator {
+            packet_version: 7,
+            packet_operator_type: Operator::Sum,
+            length_type: LengthType::Count,
+            subpackets: vec![
+                literal(0, 0),
+                literal(3, 15),
+                literal(5, 16),
+                Packet::Operator(PacketOperator {
+                    packet_version: 2,
+                    packet_operator_type: Operator::Equal,
+                    length_type: LengthType::Bits,
+                    subpackets: vec![literal(1, 2021), literal(4, usize::MAX)],
+                }),
+            ],
+        });
+        let bits = encode_packet(&packet)?;
+        assert_eq!(parse_packet(&mut bits.reader())?, packet);
+        assert_eq!(from_hex(&bits.to_hex())?, packet);
+
+        let bits = encode_packet(&literal(6, 2021))?;
+        assert_eq!((bits.len, bits.to_hex()), (21, "D2FE28".to_string()));
+        let error = encode_packet(&literal(8, 1)).unwrap_err();
+        assert_eq!(
+            error.kind,
+            BitsErrorKind::FieldTooWide { value: 8, width: 3 }
+        );
+        // Past the operator's header and the first literal
+        let error = encode_packet(&Packet::Operator(PacketOperator {
+            packet_version: 0,
+            packet_operator_type: Operator::Sum,
+            length_type: LengthType::Count,
+            subpackets: vec![literal(0, 0), literal(9, 1)],
+        }))
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Bit 29 of subpacket 1: 9 doesn't fit in 3 bits"
+        );
+        Ok(())
     }
-}
 
-fn evaluate_packet(packet: &Packet) -> usize {
-    match packet {
-        Packet::Literal(literal) => literal.value,
-        Packet::Operator(operator) => {
-            let sub_exprs: Vec<usize> = operator
-                .subpackets
-                .iter()
-                .map(|p| evaluate_packet(p))
-                .collect();
-            match operator.packet_operator_type {
-                Operator::Sum => sub_exprs.iter().sum(),
-                Operator::Product => sub_exprs.iter().product(),
-                Operator::Min => *sub_exprs.iter().min().unwrap(),
-                Operator::Max => *sub_exprs.iter().max().unwrap(),
-                Operator::Gt => (sub_exprs[0] > sub_exprs[1]) as usize,
-                Operator::Lt => (sub_exprs[0] < sub_exprs[1]) as usize,
-                Operator::Equal => (sub_exprs[0] == sub_exprs[1]) as usize,
-            }
+    #[test]
+    fn encode_examples() -> Result<()> {
+        init();
+        for hex in [
+            "D2FE28",
+            "38006F45291200",
+            "EE00D40C823060",
+            "8A004A801A8002F478",
+            "620080001611562C8802118E34",
+            "C0015000016115A2E0802F182340",
+            "A0016C880162017C3686B18A3D4780",
+        ] {
+            let packet = from_hex(hex)?;
+            let encoded = encode_packet(&packet)?.to_hex();
+            info!("{} encodes back to {}", hex, encoded);
+            // Transmissions may be padded past the last whole digit
+            assert!(hex.starts_with(&encoded));
+            assert_eq!(from_hex(&encoded)?, packet);
         }
+        Ok(())
     }
-}
 
-fn main() -> Result<()> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let mut buffer = String::new();
-    io::stdin().read_line(&mut buffer)?;
-    let mut binary_string = String::new();
-
-    for c in buffer.chars() {
-        let result = match c {
-            '0' => "0000",
-            '1' => "0001",
-            '2' => "0010",
-            '3' => "0011",
-            '4' => "0100",
-            '5' => "0101",
-            '6' => "0110",
-            '7' => "0111",
-            '8' => "1000",
-            '9' => "1001",
-            'A' => "1010",
-            'B' => "1011",
-            'C' => "1100",
-            'D' => "1101",
-            'E' => "1110",
-            'F' => "1111",
-            _ => panic!("Not hex"),
+    #[test]
+    fn reports_offsets() {
+        init();
+        assert_eq!(
+            from_hex("D2FE"),
+            Err(BitsError {
+                offset: Some(16),
+                path: vec![],
+                kind: BitsErrorKind::Truncated { wanted: 1, left: 0 }
+            })
+        );
+        let not_hex = from_hex("D2G").unwrap_err();
+        assert_eq!(not_hex.kind, BitsErrorKind::NotHex('G'));
+        assert_eq!(
+            not_hex.to_string(),
+            "Bit 8 of the outermost packet: expected a hex digit, found 'G'"
+        );
+        // An operator claiming 10 bits of subpackets, holding an 11 bit literal
+        let overrun = from_literal("0000000000000000001010".to_string() + "01010000100");
+        assert_eq!(
+            overrun.unwrap_err().kind,
+            BitsErrorKind::Overrun { end: 32 }
+        );
+        // Two subpackets, the second cut short at bit 36
+        let truncated =
+            from_literal("0000001".to_string() + "00000000010" + "01010000100" + "1001000")
+                .unwrap_err();
+        assert_eq!((truncated.offset, truncated.path), (Some(36), vec![1]));
+    }
+
+    #[test]
+    fn checks_arity() -> Result<()> {
+        init();
+        let literal = |value| {
+            Packet::Literal(PacketLiteral {
+                packet_version: 0,
+                value,
+            })
         };
-        binary_string.push_str(result);
+        let operator = |packet_operator_type, subpackets| {
+            Packet::Operator(PacketOperator {
+                packet_version: 0,
+                packet_operator_type,
+                length_type: LengthType::Count,
+                subpackets,
+            })
+        };
+        assert_eq!(
+            encode_packet(&operator(Operator::Gt, vec![literal(1)]))
+                .unwrap_err()
+                .kind,
+            BitsErrorKind::WrongArity {
+                operator: Operator::Gt,
+                subpackets: 1
+            }
+        );
+        assert!(encode_packet(&operator(Operator::Max, vec![])).is_err());
+
+        // Build the bad packets by hand, since the encoder won't
+        let mut bits = BitWriter::new();
+        bits.write(0, 3)?;
+        bits.write(Operator::Gt.code(), 3)?;
+        bits.write(1, 1)?;
+        bits.write(1, 11)?;
+        write_packet(&mut bits, &literal(1))?;
+        let error = parse_packet(&mut bits.reader()).unwrap_err();
+        assert_eq!(
+            error.kind,
+            BitsErrorKind::WrongArity {
+                operator: Operator::Gt,
+                subpackets: 1
+            }
+        );
+        assert_eq!(error.offset, Some(0));
+
+        let mut bits = BitWriter::new();
+        bits.write(0, 3)?;
+        bits.write(Operator::Min.code(), 3)?;
+        bits.write(1, 1)?;
+        bits.write(0, 11)?;
+        let error = parse_packet(&mut bits.reader()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Bit 0 of the outermost packet: Min can't take 0 subpackets"
+        );
+        Ok(())
     }
 
-    let packet = from_literal(binary_string)?;
-    println!("Versions: {}", add_versions(&packet));
+    #[test]
+    fn tolerates_case_and_newlines() -> Result<()> {
+        init();
+        let packet = from_hex("9C0141080250320F1802104A08")?;
+        assert_eq!(from_hex("9c0141080250320f1802104a08\n")?, packet);
+        assert_eq!(from_hex("9C0141080250320F1802104A08\r\n")?, packet);
+        assert_eq!(evaluate_packet(&packet)?, 1);
+        Ok(())
+    }
 
-    let result = evaluate_packet(&packet);
-    println!("Result: {}", result);
+    #[test]
+    fn reports_overflow() -> Result<()> {
+        init();
+        // The product of two literals of 2^40
+        let error = evaluate_packet(&from_hex("0600848C210842108400246108421084200")?).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "In the outermost packet: Product overflows {} bits",
+                usize::BITS
+            )
+        );
 
-    Ok(())
+        let literal = |value| {
+            Packet::Literal(PacketLiteral {
+                packet_version: 0,
+                value,
+            })
+        };
+        let sum = |subpackets| {
+            Packet::Operator(PacketOperator {
+                packet_version: 0,
+                packet_operator_type: Operator::Sum,
+                length_type: LengthType::Count,
+                subpackets,
+            })
+        };
+        let packet = sum(vec![literal(1), sum(vec![literal(usize::MAX), literal(1)])]);
+        let error = evaluate_packet(&packet).unwrap_err();
+        assert_eq!(
+            (error.offset, error.path, error.kind),
+            (
+                None,
+                vec![1],
+                BitsErrorKind::Overflow {
+                    operator: Operator::Sum
+                }
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn limits_depth() -> Result<()> {
+        init();
+        // Sums of one subpacket each, around a literal 1
+        let nested = |depth| -> Result<BitWriter> {
+            let mut bits = BitWriter::new();
+            for _ in 0..depth {
+                bits.write(0, 6)?;
+                bits.write(1, 1)?;
+                bits.write(1, 11)?;
+            }
+            write_packet(
+                &mut bits,
+                &Packet::Literal(PacketLiteral {
+                    packet_version: 0,
+                    value: 1,
+                }),
+            )?;
+            Ok(bits)
+        };
+        let packet = parse_packet(&mut nested(MAX_DEPTH)?.reader())?;
+        assert_eq!(evaluate_packet(&packet)?, 1);
+
+        let error = parse_packet(&mut nested(MAX_DEPTH + 1)?.reader()).unwrap_err();
+        assert_eq!(
+            (error.offset, error.path.len(), error.kind),
+            (
+                Some(18 * (MAX_DEPTH + 1)),
+                MAX_DEPTH + 1,
+                BitsErrorKind::TooDeep {
+                    depth: MAX_DEPTH + 1
+                }
+            )
+        );
+        Ok(())
+    }
 }
@@ -1,85 +1,84 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use grid::{Grid, Index};
 use std::collections::{HashMap, HashSet};
 use std::io;
 use std::io::BufRead;
 
+/// A single bingo square, remembering whether it has been called yet
+#[derive(Debug, Clone)]
+struct Square {
+    number: usize,
+    marked: bool,
+}
+
 struct Board {
-    height: usize,
-    length: usize,
-    number_to_position: HashMap<usize, usize>,
-    // This could just be a set?
-    positions_marked: HashSet<usize>,
+    squares: Grid<Square>,
+    // This will handle duplicates quite poorly
+    number_to_index: HashMap<usize, Index>,
 }
 
 impl Board {
-    fn new(height: usize, length: usize, board_data: Vec<Vec<usize>>) -> Self {
-        let mut number_to_position = HashMap::new();
-        for (row_idx, row) in board_data.iter().enumerate() {
-            for (idx, value) in row.iter().enumerate() {
-                // This will handle duplicates quite poorly
-                number_to_position.insert(*value, idx + (row_idx * length));
+    fn new(board_data: Vec<Vec<usize>>) -> Result<Self> {
+        let squares = Grid::from_rows(
+            board_data
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|number| Square {
+                            number,
+                            marked: false,
+                        })
+                        .collect()
+                })
+                .collect(),
+        )?;
+        let mut number_to_index = HashMap::new();
+        for y in 0..squares.height as isize {
+            for x in 0..squares.length as isize {
+                let square = squares.access(x, y).expect("Should be in bounds");
+                number_to_index.insert(square.number, Index { x, y });
             }
         }
-        Self {
-            height,
-            length,
-            number_to_position,
-            positions_marked: HashSet::new(),
-        }
+        Ok(Self {
+            squares,
+            number_to_index,
+        })
     }
+
     fn mark_board(&mut self, number: usize) {
-        match self.number_to_position.get(&number) {
-            Some(position) => {
-                self.positions_marked.insert(*position);
+        if let Some(index) = self.number_to_index.get(&number) {
+            if let Some(square) = self.squares.get_mut(index) {
+                square.marked = true;
             }
-            None => {}
         }
     }
 
     fn check_win(&self, number: usize) -> bool {
-        match self.number_to_position.get(&number) {
-            Some(position) => self.check_board_complete(*position),
+        match self.number_to_index.get(&number) {
+            Some(index) => self.check_board_complete(index),
             None => false,
         }
     }
 
-    // Assumes all rows are equal length and all columns are equal length
-    fn check_board_complete(&self, position: usize) -> bool {
-        let row_start = position - (position % self.length);
-        let column_start = position % self.length;
-
-        let column_indices: Vec<usize> = (column_start..)
-            .step_by(self.length)
-            .take(self.height)
-            .collect();
-        let row_indices: Vec<usize> = (row_start..).take(self.length).collect();
-        if self.check_group_marked(&column_indices) || self.check_group_marked(&row_indices) {
-            true
-        } else {
-            false
-        }
-    }
-
-    fn check_group_marked(&self, group: &Vec<usize>) -> bool {
-        for index in group {
-            match self.positions_marked.contains(index) {
-                false => {
-                    return false;
-                }
-                true => {}
-            }
-        }
-        true
+    fn check_board_complete(&self, index: &Index) -> bool {
+        let is_marked = |x: isize, y: isize| {
+            self.squares
+                .access(x, y)
+                .map(|square| square.marked)
+                .unwrap_or(false)
+        };
+        let column_marked = (0..self.squares.height as isize).all(|y| is_marked(index.x, y));
+        let row_marked = (0..self.squares.length as isize).all(|x| is_marked(x, index.y));
+        column_marked || row_marked
     }
 
     fn get_board_score(&self) -> usize {
-        let mut sum = 0;
-        for (number, position) in &self.number_to_position {
-            if !self.positions_marked.contains(position) {
-                sum += number;
-            }
-        }
-        sum
+        self.squares
+            .storage
+            .iter()
+            .filter(|square| !square.marked)
+            .map(|square| square.number)
+            .sum()
     }
 }
 
@@ -88,7 +87,7 @@ fn play_bingo(sequence: Vec<usize>, mut boards: Vec<Board>) -> Result<()> {
     for value in sequence {
         let mut to_remove = HashSet::new();
         for pending_board_idx in &boards_pending {
-            let mut board = boards
+            let board = boards
                 .get_mut(*pending_board_idx)
                 .expect("Board should exist");
             board.mark_board(value);
@@ -124,8 +123,8 @@ fn main() -> Result<()> {
     let mut boards = Vec::new();
     for line in lines {
         let line = line?;
-        if &line == "" {
-            boards.push(Board::new(5, 5, board_buffer));
+        if line.is_empty() {
+            boards.push(Board::new(board_buffer)?);
             board_buffer = Vec::new();
         } else {
             board_buffer.push(
@@ -1,8 +1,7 @@
 use anyhow::{anyhow, Result};
+use grid::{Index, SparseGrid};
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::cmp;
-use std::collections::HashMap;
 use std::io;
 use std::io::BufRead;
 
@@ -31,10 +30,8 @@ impl Line {
         }
         let mut numbers = Vec::new();
         for mat in RE.captures_iter(&line) {
-            for cap in mat.iter() {
-                if let Some(number) = cap {
-                    numbers.push(number.as_str().parse::<usize>().expect("Should parse"));
-                }
+            for number in mat.iter().flatten() {
+                numbers.push(number.as_str().parse::<usize>().expect("Should parse"));
             }
         }
         match numbers.len() {
@@ -62,17 +59,23 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<Line>>>();
     let lines = lines?;
 
-    let mut grid = HashMap::new();
+    let mut grid = SparseGrid::new();
     for line in &lines {
         if line.is_horizontal() {
             for x in std::cmp::min(line.a.x, line.b.x)..std::cmp::max(line.a.x, line.b.x) + 1 {
-                let point = Point { x, y: line.a.y };
-                *grid.entry(point).or_insert(0) += 1;
+                let point = Index {
+                    x: x as isize,
+                    y: line.a.y as isize,
+                };
+                *grid.get_or_insert_with(point, || 0) += 1;
             }
         } else if line.is_vertical() {
             for y in std::cmp::min(line.a.y, line.b.y)..std::cmp::max(line.a.y, line.b.y) + 1 {
-                let point = Point { x: line.a.x, y };
-                *grid.entry(point).or_insert(0) += 1;
+                let point = Index {
+                    x: line.a.x as isize,
+                    y: y as isize,
+                };
+                *grid.get_or_insert_with(point, || 0) += 1;
             }
         } else {
             let xs = if line.a.x <= line.b.x {
@@ -88,17 +91,15 @@ fn main() -> Result<()> {
             };
 
             for i in 0..xs.len() {
-                let point = Point { x: xs[i], y: ys[i] };
-                *grid.entry(point).or_insert(0) += 1;
+                let point = Index {
+                    x: xs[i] as isize,
+                    y: ys[i] as isize,
+                };
+                *grid.get_or_insert_with(point, || 0) += 1;
             }
         }
     }
-    let mut num_points = 0;
-    for (_, count) in grid {
-        if count >= 2 {
-            num_points += 1;
-        }
-    }
+    let num_points = grid.iter().filter(|(_, count)| **count >= 2).count();
 
     println!("Num points with at least 2: {}", num_points);
     Ok(())